@@ -0,0 +1,119 @@
+use anyhow::{anyhow, Error};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use rocket::http::Method;
+
+use std::iter;
+use std::str::FromStr;
+
+// Prefix of every api token, making leaked tokens easy to recognise
+const TOKEN_PREFIX: &str = "bp_";
+
+// Length of random part of api tokens
+const TOKEN_LENGTH: usize = 40;
+
+// What requests authenticated with an api token may do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenScope {
+    Read,
+    ReadWrite,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::ReadWrite => "read_write",
+        }
+    }
+
+    // Read tokens are limited to requests that don't change anything
+    pub fn allows(&self, method: Method) -> bool {
+        match self {
+            TokenScope::Read => matches!(method, Method::Get | Method::Head | Method::Options),
+            TokenScope::ReadWrite => true,
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read" => Ok(TokenScope::Read),
+            "read_write" => Ok(TokenScope::ReadWrite),
+            _ => Err(anyhow!("Unknown token scope: {}", value)),
+        }
+    }
+}
+
+// Generate a new api token
+pub fn generate_token() -> String {
+    let mut rng = thread_rng();
+    let token: String = iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(TOKEN_LENGTH)
+        .collect();
+
+    format!("{}{}", TOKEN_PREFIX, token)
+}
+
+// Token carried by an Authorization header value of
+// the form "Bearer <token>"
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let mut parts = header.splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+            let token = token.trim();
+            if token.is_empty() {
+                None
+            } else {
+                Some(token)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_scope_only_allows_safe_methods() {
+        assert!(TokenScope::Read.allows(Method::Get));
+        assert!(!TokenScope::Read.allows(Method::Post));
+        assert!(!TokenScope::Read.allows(Method::Delete));
+        assert!(TokenScope::ReadWrite.allows(Method::Put));
+    }
+
+    #[test]
+    fn scopes_parse_from_their_names() {
+        assert_eq!("read".parse::<TokenScope>().unwrap(), TokenScope::Read);
+        assert_eq!(
+            "read_write".parse::<TokenScope>().unwrap(),
+            TokenScope::ReadWrite
+        );
+        assert!("admin".parse::<TokenScope>().is_err());
+    }
+
+    #[test]
+    fn generated_tokens_are_prefixed_and_unique() {
+        let token = generate_token();
+
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + TOKEN_LENGTH);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn bearer_token_is_read_from_header() {
+        assert_eq!(bearer_token("Bearer bp_abc"), Some("bp_abc"));
+        assert_eq!(bearer_token("bearer bp_abc"), Some("bp_abc"));
+        assert_eq!(bearer_token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+        assert_eq!(bearer_token("Bearer"), None);
+    }
+}
@@ -1,19 +1,24 @@
+use crate::auth::jwks::JwksCache;
+use crate::auth::sessions::SessionStorage;
 use crate::db::models::{NewUser, User};
-use crate::db::operations::{create_user, query_user};
+use crate::db::operations::{create_user, query_user, BlockplotDbConn};
 use anyhow::{anyhow, Error};
 use chrono::{Local, Utc};
 
-use dashmap::DashMap;
-
-use jsonwebtoken::{decode, Algorithm, DecodingKey, TokenData, Validation};
+use jsonwebtoken::{decode, decode_header, Algorithm, TokenData, Validation};
 
 use rocket::config::{Config, ConfigError};
+use rocket::http::Status;
 use rocket::request::{self, FromRequest, Request};
 use rocket::State;
 use rocket_contrib::databases::diesel;
 
 use std::env;
 
+// Seconds of leeway given to exp and nbf claims, allowing
+// for clock drift between auth0 and this server
+const CLOCK_LEEWAY_SECONDS: u64 = 60;
+
 pub fn build_random_state() -> String {
     use rand::Rng;
     use rand::{distributions::Alphanumeric, thread_rng};
@@ -31,13 +36,17 @@ pub fn build_random_state() -> String {
 
 // Store various parameters needed to build authorization link
 // that fetches auth0 login page. Parameters are read from
-// Rocket.toml configuration file
+// Rocket.toml configuration file. Delete identity controls whether
+// deleting an account also deletes its auth0 identity, which needs
+// the client to be authorized for the auth0 management api
 #[derive(serde::Deserialize, Debug)]
 pub struct AuthParameters {
     pub audience: String,
     pub auth0_domain: String,
     pub client_id: String,
     pub client_secret: String,
+    #[serde(default)]
+    pub delete_identity: bool,
     pub redirect_url: String,
 }
 
@@ -76,6 +85,7 @@ impl AuthParameters {
             auth0_domain: String::from(config.get_str("auth0_domain")?),
             client_id: String::from(config.get_str("client_id")?),
             client_secret: secret.to_string(),
+            delete_identity: config.get_bool("auth0_delete_identity").unwrap_or(false),
             redirect_url: String::from(config.get_str("redirect_url")?),
         };
 
@@ -89,6 +99,7 @@ impl AuthParameters {
             auth0_domain: config.authparameters.auth0_domain,
             client_id: config.authparameters.client_id,
             client_secret: config.authparameters.client_secret,
+            delete_identity: config.authparameters.delete_identity,
             redirect_url: config.authparameters.redirect_url,
         };
 
@@ -115,33 +126,69 @@ impl AuthParameters {
             redirect_uri: self.redirect_url.clone(),
         }
     }
+
+    // Delete identity of user from auth0, so deleted accounts can't
+    // log back in. Uses a management api token fetched through
+    // client credentials grant
+    pub fn delete_auth0_identity(&self, auth_id: &str) -> Result<(), Error> {
+        let client = reqwest::blocking::Client::new();
+        let token_request = ManagementTokenRequest {
+            audience: format!("https://{}/api/v2/", self.auth0_domain),
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+            grant_type: String::from("client_credentials"),
+        };
+        let token_response: ManagementTokenResponse = client
+            .post(&format!("https://{}/oauth/token", self.auth0_domain))
+            .json(&token_request)
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        // Auth ids hold a pipe between provider and id, which
+        // has to be encoded within the url path
+        client
+            .delete(&format!(
+                "https://{}/api/v2/users/{}",
+                self.auth0_domain,
+                auth_id.replace('|', "%7C")
+            ))
+            .bearer_auth(token_response.access_token)
+            .send()?
+            .error_for_status()?;
+
+        Ok(())
+    }
 }
 
-// Take json web token, decode using json web key set, and validate
-// against proper audience/auth0 domain
+// Take json web token, verify its signature with the auth0 key named
+// by its kid header, and validate against proper audience/auth0 domain
 pub fn decode_and_validate(
     audience: &str,
     domain: &str,
     jwt: &str,
-    secret: &str,
+    jwks: &JwksCache,
 ) -> Result<TokenData<IdToken>, Error> {
-    // Decode jwt token with json web key set and validation algorithm
-    let payload = decode::<IdToken>(
-        jwt,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::new(Algorithm::HS256),
-    )
-    .unwrap();
-
-    // Validate for correct audience
-    if payload.claims.aud != audience {
-        return Err(anyhow!("Failure on audience validation"));
+    let header = decode_header(jwt)?;
+    if header.alg != Algorithm::RS256 {
+        return Err(anyhow!("Unsupported signing algorithm: {:?}", header.alg));
+    }
+    let kid = header.kid.ok_or_else(|| anyhow!("Token has no key id"))?;
+    let key = jwks.decoding_key(&kid)?;
+
+    // Validate signature, expiration, audience and auth domain
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.leeway = CLOCK_LEEWAY_SECONDS;
+    validation.set_audience(&[audience]);
+    validation.iss = Some(format!("https://{}/", domain));
+    let payload = decode::<IdToken>(jwt, &key, &validation)?;
+
+    // Auth0 leaves nbf out of id tokens, so it's only checked when present
+    if let Some(nbf) = payload.claims.nbf {
+        if nbf > Utc::now().timestamp() + CLOCK_LEEWAY_SECONDS as i64 {
+            return Err(anyhow!("Token isn't valid yet"));
+        }
     }
-
-    // Validate for correct auth domain
-    if payload.claims.iss != format!("https://{}/", domain) {
-        return Err(anyhow!("Failure on domain validation"));
-    };
 
     Ok(payload)
 }
@@ -154,8 +201,9 @@ pub fn get_or_create_user(
     jwt_payload: &TokenData<IdToken>,
 ) -> Result<User, diesel::result::Error> {
     // Query database for user. Returns Option containing user struct if found.
-    // Returns None if user not found
-    let user = query_user(db, jwt_payload.claims.sub.to_string());
+    // Returns None if user not found. Errors, such as credentials that
+    // fail to open, are passed on so no duplicate user is created
+    let user = query_user(db, jwt_payload.claims.sub.to_string())?;
 
     // Returns user database information as a Result type
     // if user variable matches Some.
@@ -199,28 +247,14 @@ pub struct IdToken {
     pub exp: i64,
     pub given_name: String,
     pub iss: String,
+    pub nbf: Option<i64>,
     pub nickname: String,
     pub picture: String,
     pub sub: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct Jwks {
-    keys: Vec<Key>,
-}
-
-#[derive(Debug, Deserialize)]
-struct Key {
-    alg: String,
-    kty: String,
-    n: String,
-    e: String,
-    kid: String,
-    x5t: String,
-    x5c: Vec<String>,
-}
-
-pub struct SessionDB(pub DashMap<String, Option<Session>>);
+// Login sessions, held by whichever store is configured
+pub struct SessionDB(pub SessionStorage);
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Session {
@@ -244,22 +278,20 @@ impl Session {
 impl<'a, 'r> FromRequest<'a, 'r> for Session {
     type Error = ();
 
-    // TODO: Need to validate expiration token
     fn from_request(request: &'a Request<'r>) -> request::Outcome<Session, ()> {
         let session_id: Option<String> = request
             .cookies()
             .get("session")
             .and_then(|cookie| cookie.value().parse().ok());
         if let Some(id) = session_id {
+            let pg_conn = request.guard::<BlockplotDbConn>().unwrap();
             let session_db = request.guard::<State<SessionDB>>().unwrap().inner();
-            // Occasionally hitting a unwrap on None panic here
-            let session_map = session_db.0.get(&id).unwrap();
-            match *session_map {
-                Some(ref session) => {
-                    return rocket::Outcome::Success(session.clone());
-                }
-                None => {
-                    return rocket::Outcome::Forward(());
+            match session_db.0.store(&pg_conn).get(&id) {
+                Ok(Some(session)) => rocket::Outcome::Success(session),
+                Ok(None) => rocket::Outcome::Forward(()),
+                Err(error) => {
+                    println!("Error reading session: {}", error);
+                    rocket::Outcome::Failure((Status::InternalServerError, ()))
                 }
             }
         } else {
@@ -278,6 +310,23 @@ pub struct TokenRequest {
     redirect_uri: String,
 }
 
+// Contains data used as parameters for /oauth/token endpoint
+// when requesting a management api token
+#[derive(Debug, Serialize)]
+struct ManagementTokenRequest {
+    audience: String,
+    client_id: String,
+    client_secret: String,
+    grant_type: String,
+}
+
+// Contains data returned from call to /oauth/token endpoint
+// for a management api token
+#[derive(Debug, Deserialize)]
+struct ManagementTokenResponse {
+    access_token: String,
+}
+
 // Contains data returned from call to /oauth/token endpoint
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TokenResponse {
@@ -293,3 +342,148 @@ pub struct UserInfo {
     pub email: String,
     pub user_id: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::auth::jwks::Jwks;
+
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    use serde_json::{json, Value};
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // Key pairs used by tests only. Public half of first key is in
+    // jwks fixture, and of both keys in rotated jwks fixture
+    const PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwks_key_1.pem");
+    const ROTATED_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwks_key_2.pem");
+    const JWKS: &str = include_str!("../../tests/fixtures/jwks.json");
+    const JWKS_ROTATED: &str = include_str!("../../tests/fixtures/jwks_rotated.json");
+
+    const AUDIENCE: &str = "client-id";
+    const DOMAIN: &str = "blockplot.test";
+
+    fn jwks() -> JwksCache {
+        JwksCache::with_fetch(|| Ok(serde_json::from_str::<Jwks>(JWKS)?))
+    }
+
+    // Claims of an id token expiring given seconds from now
+    fn claims(expires_in: i64) -> Value {
+        json!({
+            "aud": AUDIENCE,
+            "email": "test@blockplot.test",
+            "exp": Utc::now().timestamp() + expires_in,
+            "given_name": "Test",
+            "iss": format!("https://{}/", DOMAIN),
+            "nickname": "test",
+            "picture": "https://blockplot.test/test.png",
+            "sub": "auth0|test",
+        })
+    }
+
+    fn sign(claims: &Value, kid: &str) -> String {
+        sign_with(claims, kid, PRIVATE_KEY)
+    }
+
+    fn sign_with(claims: &Value, kid: &str, private_key: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let key = EncodingKey::from_rsa_pem(private_key.as_bytes()).unwrap();
+
+        encode(&header, claims, &key).unwrap()
+    }
+
+    fn validate(jwt: &str) -> Result<TokenData<IdToken>, Error> {
+        decode_and_validate(AUDIENCE, DOMAIN, jwt, &jwks())
+    }
+
+    #[test]
+    fn token_signed_with_published_key_is_decoded() {
+        let payload = validate(&sign(&claims(3600), "fixture-key-1")).unwrap();
+
+        assert_eq!(payload.claims.sub, "auth0|test");
+        assert_eq!(payload.claims.nbf, None);
+    }
+
+    #[test]
+    fn token_signed_with_rotated_key_is_decoded() {
+        let rotated = Arc::new(AtomicBool::new(false));
+        let published = rotated.clone();
+        let jwks = JwksCache::with_fetch(move || {
+            let fixture = match published.load(Ordering::SeqCst) {
+                false => JWKS,
+                true => JWKS_ROTATED,
+            };
+
+            Ok(serde_json::from_str::<Jwks>(fixture)?)
+        });
+        let jwt = sign_with(&claims(3600), "fixture-key-2", ROTATED_PRIVATE_KEY);
+
+        assert!(decode_and_validate(
+            AUDIENCE,
+            DOMAIN,
+            &sign(&claims(3600), "fixture-key-1"),
+            &jwks
+        )
+        .is_ok());
+        assert!(decode_and_validate(AUDIENCE, DOMAIN, &jwt, &jwks).is_err());
+
+        // Auth0 publishes new key, which is picked up once fetches
+        // are no longer held off
+        rotated.store(true, Ordering::SeqCst);
+        jwks.age(3600);
+        let payload = decode_and_validate(AUDIENCE, DOMAIN, &jwt, &jwks).unwrap();
+
+        assert_eq!(payload.claims.sub, "auth0|test");
+    }
+
+    #[test]
+    fn expired_token_is_rejected_after_leeway() {
+        assert!(validate(&sign(&claims(-30), "fixture-key-1")).is_ok());
+        assert!(validate(&sign(&claims(-120), "fixture-key-1")).is_err());
+    }
+
+    #[test]
+    fn token_not_yet_valid_is_rejected_after_leeway() {
+        let mut early = claims(3600);
+        early["nbf"] = json!(Utc::now().timestamp() + 30);
+        assert!(validate(&sign(&early, "fixture-key-1")).is_ok());
+
+        early["nbf"] = json!(Utc::now().timestamp() + 300);
+        assert!(validate(&sign(&early, "fixture-key-1")).is_err());
+    }
+
+    #[test]
+    fn wrong_audience_or_domain_is_rejected() {
+        let mut wrong_audience = claims(3600);
+        wrong_audience["aud"] = json!("other-client");
+        let mut wrong_domain = claims(3600);
+        wrong_domain["iss"] = json!("https://attacker.test/");
+
+        assert!(validate(&sign(&wrong_audience, "fixture-key-1")).is_err());
+        assert!(validate(&sign(&wrong_domain, "fixture-key-1")).is_err());
+    }
+
+    #[test]
+    fn malformed_or_unverifiable_tokens_are_errors() {
+        let hs256 = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(3600),
+            &EncodingKey::from_secret(b"client-secret"),
+        )
+        .unwrap();
+
+        assert!(validate("not a token").is_err());
+        assert!(validate(&hs256).is_err());
+        // Key id names a key that isn't published
+        assert!(validate(&sign(&claims(3600), "fixture-key-2")).is_err());
+
+        // Signature that doesn't match claims
+        let mut tampered = sign(&claims(3600), "fixture-key-1");
+        tampered.push('A');
+        assert!(validate(&tampered).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, Duration, Utc};
+
+use jsonwebtoken::DecodingKey;
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+// Minutes fetched keys are used for before being fetched again
+const KEYS_MAX_AGE_MINUTES: i64 = 60;
+
+// Least seconds between fetches, so tokens carrying made up key ids
+// can't flood auth0 with requests, and an unreachable auth0 isn't
+// waited on by every login
+const MIN_REFETCH_SECONDS: i64 = 60;
+
+// Seconds a fetch of keys may take before it's given up on
+const FETCH_TIMEOUT_SECONDS: u64 = 5;
+
+// Json web key set published by auth0
+#[derive(Debug, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Key>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Key {
+    pub alg: String,
+    pub kty: String,
+    pub n: String,
+    pub e: String,
+    pub kid: String,
+}
+
+type Fetch = Box<dyn Fn() -> Result<Jwks, Error> + Send + Sync>;
+
+// Signing keys of auth0 cached by key id. Managed by rocket
+// application State, so keys are shared between requests
+pub struct JwksCache {
+    fetch: Fetch,
+    cached: Mutex<CachedKeys>,
+}
+
+struct CachedKeys {
+    keys: HashMap<String, Key>,
+    fetched_at: Option<DateTime<Utc>>,
+    attempted_at: Option<DateTime<Utc>>,
+}
+
+impl JwksCache {
+    // Cache of keys published by auth0 domain
+    pub fn new(auth0_domain: &str) -> Self {
+        let jwks_url = format!("https://{}/.well-known/jwks.json", auth0_domain);
+
+        let client = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(FETCH_TIMEOUT_SECONDS))
+            .build();
+
+        JwksCache::with_fetch(move || {
+            let client = client.as_ref().map_err(|error| anyhow!("{}", error))?;
+            let jwks = client.get(&jwks_url).send()?.error_for_status()?.json()?;
+
+            Ok(jwks)
+        })
+    }
+
+    // Cache of keys returned by fetch, such as a local key set
+    pub fn with_fetch<F>(fetch: F) -> Self
+    where
+        F: Fn() -> Result<Jwks, Error> + Send + Sync + 'static,
+    {
+        JwksCache {
+            fetch: Box::new(fetch),
+            cached: Mutex::new(CachedKeys {
+                keys: HashMap::new(),
+                fetched_at: None,
+                attempted_at: None,
+            }),
+        }
+    }
+
+    // Decoding key of rsa key with given key id. Keys are fetched when
+    // cache is empty or stale, and fetched again when key id is unknown,
+    // since auth0 publishes new keys before signing tokens with them.
+    // Fetches are made without holding cache lock, and keys fetched
+    // before are still served when a fetch fails
+    pub fn decoding_key(&self, kid: &str) -> Result<DecodingKey<'static>, Error> {
+        let now = Utc::now();
+        {
+            let mut cached = self.lock()?;
+
+            let fresh = cached.fetched_at.map_or(false, |fetched_at| {
+                now - fetched_at < Duration::minutes(KEYS_MAX_AGE_MINUTES)
+            });
+            if fresh {
+                if let Some(key) = cached.keys.get(kid) {
+                    return Ok(decoding_key(key));
+                }
+            }
+
+            // Another request fetched or is fetching keys
+            let attempted = cached.attempted_at.map_or(false, |attempted_at| {
+                now - attempted_at < Duration::seconds(MIN_REFETCH_SECONDS)
+            });
+            if attempted {
+                return cached.key(kid);
+            }
+            cached.attempted_at = Some(now);
+        }
+
+        let fetched = (self.fetch)();
+        let mut cached = self.lock()?;
+        match fetched {
+            Ok(jwks) => {
+                cached.keys = jwks
+                    .keys
+                    .into_iter()
+                    .filter(|key| key.kty == "RSA")
+                    .map(|key| (key.kid.clone(), key))
+                    .collect();
+                cached.fetched_at = Some(now);
+            }
+            Err(error) => {
+                println!("Error fetching signing keys: {}", error);
+                if !cached.keys.contains_key(kid) {
+                    return Err(error);
+                }
+            }
+        }
+
+        cached.key(kid)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, CachedKeys>, Error> {
+        self.cached
+            .lock()
+            .map_err(|_| anyhow!("Key cache lock is poisoned"))
+    }
+}
+
+impl CachedKeys {
+    fn key(&self, kid: &str) -> Result<DecodingKey<'static>, Error> {
+        self.keys
+            .get(kid)
+            .map(decoding_key)
+            .ok_or_else(|| anyhow!("Unknown key id: {}", kid))
+    }
+}
+
+// Move fetch times of cache back by given seconds, as if
+// that much time had passed since keys were fetched
+#[cfg(test)]
+impl JwksCache {
+    pub(crate) fn age(&self, seconds: i64) {
+        let mut cached = self.cached.lock().unwrap();
+        cached.fetched_at = cached
+            .fetched_at
+            .map(|fetched_at| fetched_at - Duration::seconds(seconds));
+        cached.attempted_at = cached
+            .attempted_at
+            .map(|attempted_at| attempted_at - Duration::seconds(seconds));
+    }
+}
+
+fn decoding_key(key: &Key) -> DecodingKey<'static> {
+    DecodingKey::from_rsa_components(&key.n, &key.e).into_static()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const JWKS: &str = include_str!("../../tests/fixtures/jwks.json");
+    const JWKS_ROTATED: &str = include_str!("../../tests/fixtures/jwks_rotated.json");
+
+    // Cache serving fixture key set, then rotated key set on later
+    // fetches. Counter tracks how many fetches were made
+    fn fixture_cache() -> (JwksCache, Arc<AtomicUsize>) {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let cache = JwksCache::with_fetch(move || {
+            let fixture = match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => JWKS,
+                _ => JWKS_ROTATED,
+            };
+
+            Ok(serde_json::from_str(fixture)?)
+        });
+
+        (cache, fetches)
+    }
+
+    #[test]
+    fn keys_are_fetched_once_and_cached() {
+        let (cache, fetches) = fixture_cache();
+
+        assert!(cache.decoding_key("fixture-key-1").is_ok());
+        assert!(cache.decoding_key("fixture-key-1").is_ok());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn unknown_key_id_fetches_rotated_keys() {
+        let (cache, fetches) = fixture_cache();
+        cache.decoding_key("fixture-key-1").unwrap();
+
+        // Fetching again straight away is held off
+        assert!(cache.decoding_key("fixture-key-2").is_err());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        cache.age(MIN_REFETCH_SECONDS);
+        assert!(cache.decoding_key("fixture-key-2").is_ok());
+        assert!(cache.decoding_key("fixture-key-1").is_ok());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stale_keys_are_fetched_again() {
+        let (cache, fetches) = fixture_cache();
+        cache.decoding_key("fixture-key-1").unwrap();
+
+        cache.age(KEYS_MAX_AGE_MINUTES * 60);
+        cache.decoding_key("fixture-key-1").unwrap();
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stale_keys_are_served_when_fetch_fails() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let cache = JwksCache::with_fetch(move || match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => Ok(serde_json::from_str(JWKS)?),
+            _ => Err(anyhow!("Connection timed out")),
+        });
+        cache.decoding_key("fixture-key-1").unwrap();
+
+        cache.age(KEYS_MAX_AGE_MINUTES * 60);
+        assert!(cache.decoding_key("fixture-key-1").is_ok());
+        assert!(cache.decoding_key("fixture-key-2").is_err());
+
+        // Failed fetch isn't retried by every request
+        assert!(cache.decoding_key("fixture-key-1").is_ok());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fetch_errors_are_returned() {
+        let cache = JwksCache::with_fetch(|| Err(anyhow!("Connection refused")));
+
+        assert!(cache.decoding_key("fixture-key-1").is_err());
+    }
+}
@@ -1 +1,4 @@
+pub mod api_tokens;
 pub mod auth0;
+pub mod jwks;
+pub mod sessions;
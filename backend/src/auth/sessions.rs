@@ -0,0 +1,288 @@
+use crate::auth::auth0::Session;
+use crate::db::models::{NewSession, SessionRecord};
+use crate::db::operations::{
+    delete_expired_sessions, delete_session, delete_user_sessions, query_session, upsert_session,
+};
+
+use anyhow::{anyhow, Error};
+
+use chrono::{Duration, Utc};
+
+use dashmap::DashMap;
+
+use diesel::{Connection, PgConnection};
+
+use rocket::config::Config;
+
+use sha2::{Digest, Sha256};
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::thread;
+
+// Storage of login sessions, keyed by the token held in session cookie.
+// Expired sessions are treated as missing
+pub trait SessionStore {
+    fn get(&self, token: &str) -> Result<Option<Session>, Error>;
+
+    // Store session under token, replacing any session stored before
+    fn insert(&self, token: &str, session: Session) -> Result<(), Error>;
+
+    fn remove(&self, token: &str) -> Result<(), Error>;
+
+    // Remove every session of user, logging them out on all devices
+    fn remove_user(&self, auth_id: &str) -> Result<usize, Error>;
+
+    fn remove_expired(&self) -> Result<usize, Error>;
+}
+
+// Sessions held in memory. Lost on restart, and not shared
+// between backend instances. Clones share the same sessions
+#[derive(Clone)]
+pub struct MemorySessionStore(Arc<DashMap<String, Session>>);
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        MemorySessionStore(Arc::new(DashMap::new()))
+    }
+}
+
+impl Default for MemorySessionStore {
+    fn default() -> Self {
+        MemorySessionStore::new()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn get(&self, token: &str) -> Result<Option<Session>, Error> {
+        let session = self
+            .0
+            .get(token)
+            .map(|session| session.clone())
+            .filter(|session| !session.session_expired());
+
+        Ok(session)
+    }
+
+    fn insert(&self, token: &str, session: Session) -> Result<(), Error> {
+        self.0.insert(token.to_string(), session);
+
+        Ok(())
+    }
+
+    fn remove(&self, token: &str) -> Result<(), Error> {
+        self.0.remove(token);
+
+        Ok(())
+    }
+
+    fn remove_user(&self, auth_id: &str) -> Result<usize, Error> {
+        let count = self.0.len();
+        self.0.retain(|_, session| session.user_id != auth_id);
+
+        Ok(count - self.0.len())
+    }
+
+    fn remove_expired(&self) -> Result<usize, Error> {
+        let count = self.0.len();
+        self.0.retain(|_, session| !session.session_expired());
+
+        Ok(count - self.0.len())
+    }
+}
+
+// Sessions stored in sessions table, so logins survive restarts
+// and are shared by every backend instance. Borrows connection of
+// the request it's used by
+pub struct PgSessionStore<'a> {
+    conn: &'a PgConnection,
+}
+
+impl<'a> PgSessionStore<'a> {
+    pub fn new(conn: &'a PgConnection) -> Self {
+        PgSessionStore { conn }
+    }
+}
+
+impl SessionStore for PgSessionStore<'_> {
+    fn get(&self, token: &str) -> Result<Option<Session>, Error> {
+        let session = query_session(self.conn, &token_hash(token))?
+            .map(Session::from)
+            .filter(|session| !session.session_expired());
+
+        Ok(session)
+    }
+
+    fn insert(&self, token: &str, session: Session) -> Result<(), Error> {
+        upsert_session(self.conn, &new_session(token, session))?;
+
+        Ok(())
+    }
+
+    fn remove(&self, token: &str) -> Result<(), Error> {
+        delete_session(self.conn, &token_hash(token))?;
+
+        Ok(())
+    }
+
+    fn remove_user(&self, auth_id: &str) -> Result<usize, Error> {
+        Ok(delete_user_sessions(self.conn, auth_id)?)
+    }
+
+    fn remove_expired(&self) -> Result<usize, Error> {
+        Ok(delete_expired_sessions(self.conn, Utc::now().timestamp())?)
+    }
+}
+
+// Where login sessions are kept, selected by session_store in Rocket.toml
+#[derive(Clone)]
+pub enum SessionStorage {
+    Memory(MemorySessionStore),
+    Postgres,
+}
+
+impl SessionStorage {
+    // Session store to use with given connection. Connection is
+    // only used when sessions are kept in postgres
+    pub fn store<'a>(&self, conn: &'a PgConnection) -> Box<dyn SessionStore + 'a> {
+        match self {
+            SessionStorage::Memory(store) => Box::new(store.clone()),
+            SessionStorage::Postgres => Box::new(PgSessionStore::new(conn)),
+        }
+    }
+}
+
+impl From<SessionRecord> for Session {
+    fn from(record: SessionRecord) -> Self {
+        Session {
+            block_count: record.block_count,
+            email: record.email,
+            expires: record.expires,
+            given_name: record.given_name,
+            key_present: record.key_present,
+            nickname: record.nickname,
+            picture: record.picture,
+            user_id: record.auth_id,
+        }
+    }
+}
+
+fn new_session(token: &str, session: Session) -> NewSession {
+    NewSession {
+        token_hash: token_hash(token),
+        auth_id: session.user_id,
+        block_count: session.block_count,
+        email: session.email,
+        expires: session.expires,
+        given_name: session.given_name,
+        key_present: session.key_present,
+        nickname: session.nickname,
+        picture: session.picture,
+    }
+}
+
+// Hex encoded sha256 hash of a token. Session and api tokens
+// are stored by their hash only
+pub fn token_hash(token: &str) -> String {
+    let mut hash = String::new();
+    for byte in Sha256::digest(token.as_bytes()).iter() {
+        let _ = write!(hash, "{:02x}", byte);
+    }
+
+    hash
+}
+
+// Session storage selected by session_store in Rocket.toml. Sessions
+// are kept in postgres unless it's set to memory
+pub fn configured_storage(config: &Config) -> Result<SessionStorage, Error> {
+    match config.get_str("session_store").unwrap_or("postgres") {
+        "memory" => Ok(SessionStorage::Memory(MemorySessionStore::new())),
+        "postgres" => Ok(SessionStorage::Postgres),
+        other => Err(anyhow!("Unknown session store: {}", other)),
+    }
+}
+
+// Spawn thread removing expired sessions from storage every interval.
+// Runs outside of requests, so it opens its own connection each time
+pub fn spawn_session_cleanup(storage: SessionStorage, database_url: String, interval: Duration) {
+    let wait = interval
+        .to_std()
+        .unwrap_or_else(|_| std::time::Duration::from_secs(3600));
+
+    thread::spawn(move || loop {
+        thread::sleep(wait);
+        let conn = match PgConnection::establish(&database_url) {
+            Ok(conn) => conn,
+            Err(error) => {
+                println!("Session cleanup failed to connect to database: {}", error);
+                continue;
+            }
+        };
+        let store = storage.store(&conn);
+        match store.remove_expired() {
+            Ok(count) => println!("Removed {} expired session(s)", count),
+            Err(error) => println!("Error removing expired sessions: {}", error),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(user_id: &str, expires_in: i64) -> Session {
+        Session {
+            block_count: 1,
+            email: String::from("test@blockplot.test"),
+            expires: Utc::now().timestamp() + expires_in,
+            given_name: String::from("Test"),
+            key_present: false,
+            nickname: String::from("test"),
+            picture: String::from("https://blockplot.test/test.png"),
+            user_id: user_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn stored_sessions_are_returned_until_removed() {
+        let store = MemorySessionStore::new();
+        store.insert("token", session("auth0|test", 3600)).unwrap();
+
+        assert_eq!(store.get("token").unwrap().unwrap().user_id, "auth0|test");
+        assert!(store.get("unknown").unwrap().is_none());
+
+        store.remove("token").unwrap();
+        assert!(store.get("token").unwrap().is_none());
+    }
+
+    #[test]
+    fn expired_sessions_are_missing_and_cleaned_up() {
+        let store = MemorySessionStore::new();
+        store.insert("expired", session("auth0|test", -1)).unwrap();
+        store.insert("active", session("auth0|test", 3600)).unwrap();
+
+        assert!(store.get("expired").unwrap().is_none());
+        assert_eq!(store.remove_expired().unwrap(), 1);
+        assert!(store.get("active").unwrap().is_some());
+    }
+
+    #[test]
+    fn removing_user_logs_out_every_device() {
+        let store = MemorySessionStore::new();
+        store.insert("laptop", session("auth0|test", 3600)).unwrap();
+        store.insert("phone", session("auth0|test", 3600)).unwrap();
+        store.insert("other", session("auth0|other", 3600)).unwrap();
+
+        assert_eq!(store.remove_user("auth0|test").unwrap(), 2);
+        assert!(store.get("laptop").unwrap().is_none());
+        assert!(store.get("other").unwrap().is_some());
+    }
+
+    #[test]
+    fn token_hash_is_hex_encoded_sha256() {
+        assert_eq!(
+            token_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use backend::credentials::keyring;
+use backend::db::operations::{lock_user, query_users_with_credentials, update_user_credentials};
+
+use anyhow::Error;
+
+use diesel::{Connection, PgConnection};
+
+use std::env;
+use std::process;
+
+// Seal stored credentials with credentials.keys from configuration,
+// or APP_CREDENTIALS__KEYS when set in environment. Rows still
+// holding plaintext credentials are sealed under a fresh data key,
+// and data keys wrapped with an older key are rewrapped with the
+// current one. Safe to run again, rows already up to date are skipped.
+// Reads database url from DATABASE_URL environment variable
+fn main() {
+    use dotenv::dotenv;
+    dotenv().ok();
+
+    let keyring = keyring().unwrap_or_else(|error| exit(error));
+    let database_url = env::var("DATABASE_URL")
+        .unwrap_or_else(|_| exit("DATABASE_URL environment variable isn't set"));
+    let conn = PgConnection::establish(&database_url).unwrap_or_else(|error| exit(error));
+
+    let users = query_users_with_credentials(&conn).unwrap_or_else(|error| exit(error));
+
+    let mut sealed = 0;
+    let mut rotated = 0;
+    for user in &users {
+        // Each row is updated in its own transaction, so an interrupted
+        // run can pick up where it stopped. Row is locked and read again,
+        // so credentials saved since it was listed aren't overwritten
+        let outcome = conn.transaction::<_, Error, _>(|| {
+            let user = lock_user(&conn, user.user_id)?;
+            let changes = if user.wrapped_data_key.is_none() {
+                Some((Outcome::Sealed, keyring.seal_credentials(&user)?))
+            } else {
+                keyring
+                    .rewrap(&user)?
+                    .map(|changes| (Outcome::Rotated, changes))
+            };
+
+            match changes {
+                Some((outcome, changes)) => {
+                    update_user_credentials(&conn, user.user_id, &changes)?;
+                    Ok(Some(outcome))
+                }
+                None => Ok(None),
+            }
+        });
+
+        match outcome {
+            Ok(Some(Outcome::Sealed)) => sealed += 1,
+            Ok(Some(Outcome::Rotated)) => rotated += 1,
+            Ok(None) => {}
+            Err(error) => exit(format!("Error sealing user {}: {}", user.user_id, error)),
+        }
+    }
+
+    println!(
+        "Sealed {} and rotated {} of {} users with credentials, current key is {}",
+        sealed,
+        rotated,
+        users.len(),
+        keyring.current_id()
+    );
+}
+
+// What was done to credentials of a row
+enum Outcome {
+    Sealed,
+    Rotated,
+}
+
+fn exit<T: std::fmt::Display, U>(error: T) -> U {
+    println!("Error: {}", error);
+    process::exit(1)
+}
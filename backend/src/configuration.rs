@@ -3,6 +3,8 @@ use serde_aux::field_attributes::deserialize_number_from_string;
 #[derive(serde::Deserialize)]
 pub struct Settings {
     pub database: DatabaseSettings,
+    #[serde(default)]
+    pub credentials: CredentialSettings,
 }
 
 #[derive(serde::Deserialize)]
@@ -16,6 +18,16 @@ pub struct DatabaseSettings {
     pub require_ssl: bool,
 }
 
+// Keys credentials are sealed with, as comma separated id=key pairs
+// where each key is 32 base64 encoded bytes. First key is the current
+// one. Keys after it are only used to open data keys wrapped before
+// a rotation. Never set in base configuration, so deployments have
+// to provide their own
+#[derive(Default, serde::Deserialize)]
+pub struct CredentialSettings {
+    pub keys: Option<String>,
+}
+
 impl DatabaseSettings {
     pub fn without_db(&self) -> String {
         let db_uri = format!(
@@ -37,14 +49,33 @@ impl DatabaseSettings {
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
+    read_configuration(&["base", "local"])
+}
+
+// Configuration used by integration tests, which also reads test
+// file holding credential keys meant for tests only
+pub fn get_test_configuration() -> Result<Settings, config::ConfigError> {
+    read_configuration(&["base", "local", "test"])
+}
+
+fn read_configuration(files: &[&str]) -> Result<Settings, config::ConfigError> {
+    use dotenv::dotenv;
+    dotenv().ok();
+
     // Initialize configuration reader
     let mut settings = config::Config::default();
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let configuration_directory = base_path.join("configuration");
 
     // Parse and store values from configuration files
-    settings.merge(config::File::from(configuration_directory.join("base")).required(true))?;
-    settings.merge(config::File::from(configuration_directory.join("local")).required(true))?;
+    for file in files {
+        settings.merge(config::File::from(configuration_directory.join(file)).required(true))?;
+    }
+
+    // Values from environment take precedence, so deployments can keep
+    // secrets out of configuration files. For example
+    // APP_CREDENTIALS__KEYS sets credentials.keys
+    settings.merge(config::Environment::with_prefix("app").separator("__"))?;
 
     settings.try_into()
 }
@@ -0,0 +1,344 @@
+use crate::configuration::get_configuration;
+use crate::db::models::{CredentialChanges, User};
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::Aes256Gcm;
+
+use anyhow::{anyhow, Error};
+
+use once_cell::sync::OnceCell;
+
+use std::convert::TryInto;
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+
+static KEYRING: OnceCell<Keyring> = OnceCell::new();
+
+// Keyring read from credentials.keys in configuration on first use,
+// and kept for the lifetime of the process
+pub fn keyring() -> Result<&'static Keyring, Error> {
+    KEYRING.get_or_try_init(|| {
+        let settings = get_configuration()
+            .map_err(|error| anyhow!("Error reading configuration: {}", error))?;
+        let keys = settings
+            .credentials
+            .keys
+            .ok_or_else(|| anyhow!("No credential keys configured, set APP_CREDENTIALS__KEYS"))?;
+
+        Keyring::parse(&keys)
+    })
+}
+
+// Use given keys instead of ones from configuration. Has no effect
+// once keyring has been read
+pub fn install_keyring(keys: &str) -> Result<&'static Keyring, Error> {
+    KEYRING.get_or_try_init(|| Keyring::parse(keys))
+}
+
+// Master keys that wrap the data key of each user. Every user
+// row holds its own data key, which seals the credentials of that
+// row. Rotating master keys only rewraps data keys, so stored
+// credentials never need to be resealed
+pub struct Keyring {
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl Keyring {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut keys = Vec::new();
+        for pair in value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let mut parts = pair.splitn(2, '=');
+            let id = parts.next().unwrap_or("").trim();
+            let encoded = parts
+                .next()
+                .ok_or_else(|| anyhow!("Credential key {} has no value", id))?;
+            if id.is_empty() {
+                return Err(anyhow!("Credential key is missing an id"));
+            }
+
+            let bytes = base64::decode(encoded.trim())?;
+            if bytes.len() != KEY_LENGTH {
+                return Err(anyhow!(
+                    "Credential key {} must be {} bytes long",
+                    id,
+                    KEY_LENGTH
+                ));
+            }
+            let cipher = Aes256Gcm::new_varkey(&bytes)
+                .map_err(|_| anyhow!("Credential key {} is invalid", id))?;
+            keys.push((id.to_string(), cipher));
+        }
+
+        if keys.is_empty() {
+            return Err(anyhow!("No credential keys configured"));
+        }
+
+        Ok(Keyring { keys })
+    }
+
+    // Id of key new data keys are wrapped with
+    pub fn current_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    // Wrap data key with current key. Key id is bound to wrapped
+    // key, so it can't be passed off as wrapped by another key
+    pub fn wrap(&self, data_key: &DataKey) -> Result<String, Error> {
+        let (id, cipher) = &self.keys[0];
+
+        seal(cipher, &data_key.0, id.as_bytes())
+    }
+
+    pub fn unwrap_key(&self, key_id: &str, wrapped: &str) -> Result<DataKey, Error> {
+        let (_, cipher) = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .ok_or_else(|| anyhow!("Unknown credential key: {}", key_id))?;
+
+        let bytes = open(cipher, wrapped, key_id.as_bytes())?;
+        if bytes.len() != KEY_LENGTH {
+            return Err(anyhow!("Wrapped data key has wrong length"));
+        }
+        let mut key = [0; KEY_LENGTH];
+        key.copy_from_slice(&bytes);
+
+        Ok(DataKey(key))
+    }
+
+    // Rewrap data key of user with current key. Returns None
+    // when data key is already wrapped with current key
+    pub fn rewrap(&self, user: &User) -> Result<Option<CredentialChanges>, Error> {
+        let (key_id, wrapped) = match (&user.credentials_key_id, &user.wrapped_data_key) {
+            (Some(key_id), Some(wrapped)) => (key_id, wrapped),
+            _ => return Err(anyhow!("User {} has no data key", user.user_id)),
+        };
+        if key_id == self.current_id() {
+            return Ok(None);
+        }
+
+        let data_key = self.unwrap_key(key_id, wrapped)?;
+
+        Ok(Some(CredentialChanges {
+            api_key: user.api_key.clone(),
+            toggl_api_key: user.toggl_api_key.clone(),
+            wakatime_api_key: user.wakatime_api_key.clone(),
+            credentials_key_id: Some(self.current_id().to_string()),
+            wrapped_data_key: Some(self.wrap(&data_key)?),
+        }))
+    }
+
+    // Seal credentials of user under a fresh data key,
+    // wrapped with current key. Credentials are given in plaintext
+    pub fn seal_credentials(&self, user: &User) -> Result<CredentialChanges, Error> {
+        let data_key = DataKey::generate();
+        let cipher = data_key.cipher();
+        let seal_column = |column: &str, value: &Option<String>| {
+            value
+                .as_ref()
+                .map(|value| seal(&cipher, value.as_bytes(), column.as_bytes()))
+                .transpose()
+        };
+
+        Ok(CredentialChanges {
+            api_key: seal_column("api_key", &user.api_key)?,
+            toggl_api_key: seal_column("toggl_api_key", &user.toggl_api_key)?,
+            wakatime_api_key: seal_column("wakatime_api_key", &user.wakatime_api_key)?,
+            credentials_key_id: Some(self.current_id().to_string()),
+            wrapped_data_key: Some(self.wrap(&data_key)?),
+        })
+    }
+
+    // Open sealed credentials of user in place. Rows without a data
+    // key were stored before credentials were encrypted, and still
+    // hold plaintext, so they're left as they are
+    pub fn open_credentials(&self, mut user: User) -> Result<User, Error> {
+        let data_key = match (&user.credentials_key_id, &user.wrapped_data_key) {
+            (Some(key_id), Some(wrapped)) => self.unwrap_key(key_id, wrapped)?,
+            _ => return Ok(user),
+        };
+        let cipher = data_key.cipher();
+        let open_column = |column: &str, value: &Option<String>| {
+            value
+                .as_ref()
+                .map(|value| {
+                    let bytes = open(&cipher, value, column.as_bytes())?;
+                    String::from_utf8(bytes).map_err(Error::from)
+                })
+                .transpose()
+        };
+
+        user.api_key = open_column("api_key", &user.api_key)?;
+        user.toggl_api_key = open_column("toggl_api_key", &user.toggl_api_key)?;
+        user.wakatime_api_key = open_column("wakatime_api_key", &user.wakatime_api_key)?;
+
+        Ok(user)
+    }
+}
+
+// Key credentials of a single user are sealed with
+pub struct DataKey([u8; KEY_LENGTH]);
+
+impl DataKey {
+    fn generate() -> Self {
+        DataKey(rand::random())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new((&self.0).into())
+    }
+}
+
+// Encrypt bytes under a random nonce. Sealed value is the base64
+// encoded nonce followed by ciphertext. Associated data names what
+// is sealed, so sealed values can't be swapped around
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8], aad: &[u8]) -> Result<String, Error> {
+    let nonce: [u8; NONCE_LENGTH] = rand::random();
+    let ciphertext = cipher
+        .encrypt(
+            (&nonce).into(),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("Error sealing credential"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+
+    Ok(base64::encode(sealed))
+}
+
+fn open(cipher: &Aes256Gcm, sealed: &str, aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let bytes = base64::decode(sealed)?;
+    if bytes.len() < NONCE_LENGTH {
+        return Err(anyhow!("Sealed credential is too short"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    let nonce: [u8; NONCE_LENGTH] = nonce.try_into()?;
+
+    cipher
+        .decrypt(
+            (&nonce).into(),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("Error opening credential"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    // Keys used by tests. Never used outside of them
+    const OLD_KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const NEW_KEY: &str = "ICEiIyQlJicoKSorLC0uLzAxMjM0NTY3ODk6Ozw9Pj8=";
+
+    fn user() -> User {
+        let timestamp = NaiveDate::from_ymd_opt(2021, 9, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        User {
+            user_id: 7,
+            auth_id: String::from("auth0|test"),
+            api_key: Some(String::from("rescuetime-key")),
+            key_present: true,
+            block_count: 1,
+            blocks_last_fetched: timestamp,
+            created_at: timestamp,
+            last_login: timestamp,
+            toggl_api_key: None,
+            wakatime_api_key: Some(String::from("wakatime-key")),
+            credentials_key_id: None,
+            wrapped_data_key: None,
+        }
+    }
+
+    fn sealed_user(keyring: &Keyring) -> User {
+        let changes = keyring.seal_credentials(&user()).unwrap();
+
+        User {
+            api_key: changes.api_key,
+            toggl_api_key: changes.toggl_api_key,
+            wakatime_api_key: changes.wakatime_api_key,
+            credentials_key_id: changes.credentials_key_id,
+            wrapped_data_key: changes.wrapped_data_key,
+            ..user()
+        }
+    }
+
+    #[test]
+    fn sealed_credentials_open_to_plaintext() {
+        let keyring = Keyring::parse(&format!("2021a={}", OLD_KEY)).unwrap();
+        let sealed = sealed_user(&keyring);
+
+        assert_ne!(sealed.api_key.as_deref(), Some("rescuetime-key"));
+        assert_eq!(sealed.toggl_api_key, None);
+        assert_eq!(sealed.credentials_key_id.as_deref(), Some("2021a"));
+
+        let opened = keyring.open_credentials(sealed).unwrap();
+        assert_eq!(opened.api_key.as_deref(), Some("rescuetime-key"));
+        assert_eq!(opened.wakatime_api_key.as_deref(), Some("wakatime-key"));
+    }
+
+    #[test]
+    fn plaintext_rows_are_left_as_they_are() {
+        let keyring = Keyring::parse(&format!("2021a={}", OLD_KEY)).unwrap();
+        let opened = keyring.open_credentials(user()).unwrap();
+
+        assert_eq!(opened.api_key.as_deref(), Some("rescuetime-key"));
+    }
+
+    #[test]
+    fn swapped_credentials_fail_to_open() {
+        let keyring = Keyring::parse(&format!("2021a={}", OLD_KEY)).unwrap();
+        let mut sealed = sealed_user(&keyring);
+        sealed.toggl_api_key = sealed.api_key.clone();
+
+        assert!(keyring.open_credentials(sealed).is_err());
+    }
+
+    #[test]
+    fn rotation_rewraps_data_key_with_current_key() {
+        let old_keyring = Keyring::parse(&format!("2021a={}", OLD_KEY)).unwrap();
+        let sealed = sealed_user(&old_keyring);
+
+        let keyring = Keyring::parse(&format!("2021b={}, 2021a={}", NEW_KEY, OLD_KEY)).unwrap();
+        let changes = keyring.rewrap(&sealed).unwrap().unwrap();
+
+        // Credentials stay as they are, only data key is rewrapped
+        assert_eq!(changes.api_key, sealed.api_key);
+        assert_eq!(changes.credentials_key_id.as_deref(), Some("2021b"));
+        let rotated = User {
+            credentials_key_id: changes.credentials_key_id,
+            wrapped_data_key: changes.wrapped_data_key,
+            ..sealed
+        };
+        assert!(keyring.rewrap(&rotated).unwrap().is_none());
+
+        // Old key is no longer needed once every row is rotated
+        let new_keyring = Keyring::parse(&format!("2021b={}", NEW_KEY)).unwrap();
+        let opened = new_keyring.open_credentials(rotated).unwrap();
+        assert_eq!(opened.api_key.as_deref(), Some("rescuetime-key"));
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        assert!(Keyring::parse("").is_err());
+        assert!(Keyring::parse("2021a").is_err());
+        assert!(Keyring::parse(&format!("={}", OLD_KEY)).is_err());
+        assert!(Keyring::parse("2021a=c2hvcnQ=").is_err());
+    }
+}
@@ -1,23 +1,43 @@
+use crate::auth::api_tokens::{bearer_token, TokenScope};
 use crate::auth::auth0::SessionDB;
-use chrono::naive::NaiveDateTime;
+use crate::auth::sessions::token_hash;
+use crate::sources::TimeSourceKind;
+use chrono::naive::{NaiveDate, NaiveDateTime};
 use diesel::Queryable;
-use rocket::http::Status;
-use rocket::request::{self, FromRequest, Request};
+use rocket::http::{Method, RawStr, Status};
+use rocket::request::{self, FromFormValue, FromParam, FromRequest, Request};
 use rocket::State;
 use serde::Deserialize;
 use std::collections::HashMap;
 
 use super::{
-    operations::{query_user, BlockplotDbConn},
-    schema::{date_times, skillblocks, users},
+    operations::{query_active_api_token, query_user, update_api_token_last_used, BlockplotDbConn},
+    schema::{
+        api_tokens, date_times, deletion_tokens, goals, sessions, skillblocks, sync_ranges, users,
+    },
 };
 
 #[derive(Deserialize, Serialize)]
 pub struct TimeData {
+    pub block_id: i32,
     pub category: String,
     pub skill_name: String,
     pub skill_description: String,
     pub time_data: HashMap<NaiveDateTime, i32>,
+    pub last_synced_at: Option<NaiveDateTime>,
+    pub sync_status: String,
+    pub appearance: Appearance,
+}
+
+// How block grid of a skillblock is shaded. Colours hold the
+// no time data colour followed by one colour per shade, thresholds
+// hold the upper bound in minutes of every shade but the last
+#[derive(Deserialize, Serialize)]
+pub struct Appearance {
+    pub palette: String,
+    pub colors: Vec<String>,
+    pub intensity_scale: String,
+    pub thresholds: Vec<i32>,
 }
 
 // Prototype wrapper struct for storing multiple TimeData requests
@@ -26,16 +46,128 @@ pub struct TimeWrapper {
     pub data: Vec<TimeData>,
 }
 
-// Struct for skillblock create request
-#[derive(FromForm)]
+// Struct for skillblock create request. Sent as
+// form data by the html form, or as json
+#[derive(Deserialize, FromForm)]
 pub struct FormData {
     pub api_key: Option<String>,
     pub category: String,
+    #[serde(default)]
     pub offline_category: bool,
     pub description: String,
     pub skill_name: String,
+    pub source: Option<String>,
+    pub toggl_api_key: Option<String>,
+    pub wakatime_api_key: Option<String>,
+}
+
+// Problem with a single field of a request. Code is
+// meant for clients, message is meant for people
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// Struct for skillblock edit request. Fields left
+// out of the request body are kept as is
+#[derive(AsChangeset, Deserialize)]
+#[table_name = "skillblocks"]
+pub struct SkillblockChanges {
+    pub category: Option<String>,
+    pub offline_category: Option<bool>,
+    pub skill_name: Option<String>,
+    #[column_name = "skill_description"]
+    pub description: Option<String>,
+    pub palette: Option<String>,
+    pub intensity_scale: Option<String>,
+    pub thresholds: Option<Vec<i32>>,
 }
 
+// Struct for manual time entry create request
+#[derive(Deserialize)]
+pub struct EntryData {
+    pub day_date: NaiveDate,
+    pub minutes: i32,
+}
+
+// Struct for manual time entry update request
+#[derive(Deserialize)]
+pub struct EntryTime {
+    pub minutes: i32,
+}
+
+// Public link to grid of a shared skillblock
+#[derive(Deserialize, Serialize)]
+pub struct ShareLink {
+    pub share_token: String,
+    pub svg_url: String,
+}
+
+// Confirmation token issued ahead of account deletion
+#[derive(Deserialize, Serialize)]
+pub struct DeletionConfirmation {
+    pub confirmation_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+// Struct for account deletion request. Token has to match
+// the one last issued to user
+#[derive(Deserialize, Serialize)]
+pub struct AccountDeletion {
+    pub confirmation_token: String,
+}
+
+// Struct for api token create request. Scope is
+// either "read" or "read_write"
+#[derive(Deserialize)]
+pub struct ApiTokenData {
+    pub name: String,
+    pub scope: String,
+}
+
+// Newly created api token. Only its hash is stored,
+// so token is shown this once and can't be retrieved later
+#[derive(Deserialize, Serialize)]
+pub struct IssuedApiToken {
+    pub token: String,
+    pub api_token: ApiToken,
+}
+
+// Struct for skillblock goal set request
+#[derive(Deserialize)]
+pub struct GoalData {
+    pub period: String,
+    pub target_minutes: i32,
+}
+
+// Wrapper allowing day dates formatted as YYYY-MM-DD
+// to be used as route and query parameters
+pub struct DayDate(pub NaiveDate);
+
+impl<'a> FromParam<'a> for DayDate {
+    type Error = &'a RawStr;
+
+    fn from_param(param: &'a RawStr) -> Result<Self, Self::Error> {
+        NaiveDate::parse_from_str(param.as_str(), "%Y-%m-%d")
+            .map(DayDate)
+            .map_err(|_| param)
+    }
+}
+
+impl<'v> FromFormValue<'v> for DayDate {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        DayDate::from_param(form_value)
+    }
+}
+
+// Optional query parameter, holding the raw value when it fails to
+// parse. Lets routes reject bad values rather than ignore them
+pub type QueryParam<'a, T> = Option<Result<T, &'a RawStr>>;
+
 #[derive(Associations, Identifiable, Queryable, Deserialize, Serialize)]
 #[belongs_to(Skillblock, foreign_key = "block_id")]
 pub struct DateTime {
@@ -56,10 +188,60 @@ pub struct Skillblock {
     pub offline_category: bool,
     pub skill_name: String,
     pub description: String,
+    pub source: String,
+    pub last_synced_at: Option<NaiveDateTime>,
+    pub sync_status: String,
+    pub palette: String,
+    pub intensity_scale: String,
+    pub thresholds: Vec<i32>,
+    pub share_token: Option<String>,
+    pub sync_failures: i32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+}
+
+// Struct for querying date ranges of a skillblock
+// that have been fully fetched from its time source
+#[derive(Associations, Identifiable, Queryable, Deserialize, Serialize)]
+#[belongs_to(Skillblock, foreign_key = "block_id")]
+pub struct SyncRange {
+    pub id: i32,
+    pub block_id: i32,
+    pub range_start: NaiveDate,
+    pub range_end: NaiveDate,
+    pub completed_at: NaiveDateTime,
+}
+
+// Struct for querying time target of a skillblock. Period
+// is either "daily" or "weekly"
+#[derive(Associations, Identifiable, Queryable, Deserialize, Serialize)]
+#[belongs_to(Skillblock, foreign_key = "block_id")]
+pub struct Goal {
+    pub id: i32,
+    pub block_id: i32,
+    pub period: String,
+    pub target_minutes: i32,
+    pub created_at: NaiveDateTime,
+}
+
+// Struct for querying personal access token of user. Hash of
+// token is never sent back to client
+#[derive(Associations, Identifiable, Queryable, Deserialize, Serialize)]
+#[belongs_to(User, foreign_key = "user_id")]
+pub struct ApiToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub scope: String,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
 }
 
 // Struct for querying user information from postgres database
-// Is also a request guard for various endpoints
+// Is also a request guard for various endpoints. Credentials are
+// sealed in the database, and opened by queries returning users
 #[derive(Identifiable, Queryable, Deserialize, Serialize)]
 #[primary_key(user_id)]
 pub struct User {
@@ -71,49 +253,164 @@ pub struct User {
     pub blocks_last_fetched: NaiveDateTime,
     pub created_at: NaiveDateTime,
     pub last_login: NaiveDateTime,
+    pub toggl_api_key: Option<String>,
+    pub wakatime_api_key: Option<String>,
+    pub credentials_key_id: Option<String>,
+    pub wrapped_data_key: Option<String>,
+}
+
+impl User {
+    // Whether user has stored credentials for a time source.
+    // Manual skillblocks need none
+    pub fn has_source_key(&self, source: TimeSourceKind) -> bool {
+        match source {
+            TimeSourceKind::RescueTime => self.key_present,
+            TimeSourceKind::Toggl => self.toggl_api_key.is_some(),
+            TimeSourceKind::WakaTime => self.wakatime_api_key.is_some(),
+            TimeSourceKind::Manual => true,
+        }
+    }
+}
+
+// Struct for storing sealed credentials of user, along with
+// the data key they're sealed with. Every column is written,
+// so credentials that aren't set are stored as null
+#[derive(AsChangeset)]
+#[table_name = "users"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct CredentialChanges {
+    pub api_key: Option<String>,
+    pub toggl_api_key: Option<String>,
+    pub wakatime_api_key: Option<String>,
+    pub credentials_key_id: Option<String>,
+    pub wrapped_data_key: Option<String>,
 }
 
 // Requst guard implementation. Validation policy will
 // check for session, determine if session is associated with a logged user
-// and verify if session is still valid.
+// and verify if session is still valid. Headless clients authenticate
+// with an api token instead of a session cookie
 impl<'a, 'r> FromRequest<'a, 'r> for User {
     type Error = ();
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<User, ()> {
         // Connection pool used to interact with postgres database.
         let pg_conn = request.guard::<BlockplotDbConn>().unwrap();
-        let session_id: Option<String> = request
-            .cookies()
-            .get("session")
-            .and_then(|cookie| cookie.value().parse().ok());
-
-        match session_id {
-            Some(id) => {
-                // Grab in memory sessions owning database. Use session id retrived from
-                // cookies to query for a valid session
-                let session_db = request.guard::<State<SessionDB>>().unwrap().inner();
-                let session_map = session_db.0.get(&id).unwrap();
-                // Check for Session struct associated with session key.
-                // If none found, forward to login endpoint
-                match *session_map {
-                    Some(ref session) => {
-                        if session.session_expired() {
-                            return rocket::Outcome::Failure((Status::Unauthorized, ()));
-                        }
-
-                        // Query postgres database for user. If match found,
-                        // return Success outcome, passing retrived User to
-                        // calling endpoint
-                        let pg_user = query_user(&pg_conn, session.user_id.to_string());
-                        match pg_user {
-                            Some(user) => rocket::Outcome::Success(user),
-                            None => rocket::Outcome::Failure((Status::InternalServerError, ())),
-                        }
-                    }
-                    None => rocket::Outcome::Failure((Status::Unauthorized, ())),
+
+        // Authorization headers of other schemes, such as basic auth
+        // sent along by a browser, are left for session cookie to decide
+        if let Some(token) = request_bearer_token(request) {
+            return api_token_user(&pg_conn, token, request.method());
+        }
+
+        session_user(request, &pg_conn)
+    }
+}
+
+// User logged in with a session cookie. Guards routes api tokens must
+// not reach, such as token management, sessions and account deletion,
+// so a leaked token can't be used to take over the account
+pub struct SessionUser(pub User);
+
+impl<'a, 'r> FromRequest<'a, 'r> for SessionUser {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<SessionUser, ()> {
+        if request_bearer_token(request).is_some() {
+            return rocket::Outcome::Failure((Status::Forbidden, ()));
+        }
+        let pg_conn = request.guard::<BlockplotDbConn>().unwrap();
+
+        session_user(request, &pg_conn).map(SessionUser)
+    }
+}
+
+// Api token of request, if it carries one in its Authorization header
+fn request_bearer_token<'r>(request: &'r Request) -> Option<&'r str> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(bearer_token)
+}
+
+// Look up user of session cookie
+fn session_user(request: &Request, pg_conn: &BlockplotDbConn) -> request::Outcome<User, ()> {
+    let session_id: Option<String> = request
+        .cookies()
+        .get("session")
+        .and_then(|cookie| cookie.value().parse().ok());
+
+    match session_id {
+        Some(id) => {
+            // Grab sessions store. Use session id retrived from
+            // cookies to query for a valid session. Unknown or expired
+            // sessions, such as ones logged out on another device,
+            // fail with unauthorized
+            let session_db = request.guard::<State<SessionDB>>().unwrap().inner();
+            let session = match session_db.0.store(pg_conn).get(&id) {
+                Ok(Some(session)) => session,
+                Ok(None) => return rocket::Outcome::Failure((Status::Unauthorized, ())),
+                Err(error) => {
+                    println!("Error reading session: {}", error);
+                    return rocket::Outcome::Failure((Status::InternalServerError, ()));
                 }
+            };
+            if session.session_expired() {
+                return rocket::Outcome::Failure((Status::Unauthorized, ()));
             }
-            None => rocket::Outcome::Failure((Status::Unauthorized, ())),
+
+            // Query postgres database for user. If match found,
+            // return Success outcome, passing retrived User to
+            // calling endpoint
+            let pg_user = query_user(pg_conn, session.user_id.to_string());
+            match pg_user {
+                Ok(Some(user)) => rocket::Outcome::Success(user),
+                Ok(None) => rocket::Outcome::Failure((Status::InternalServerError, ())),
+                Err(error) => {
+                    println!("Error reading user: {}", error);
+                    rocket::Outcome::Failure((Status::InternalServerError, ()))
+                }
+            }
+        }
+        None => rocket::Outcome::Failure((Status::Unauthorized, ())),
+    }
+}
+
+// Look up user of api token carried by Authorization header. Unknown
+// or revoked tokens fail with unauthorized, and requests outside
+// of token scope with forbidden
+fn api_token_user(
+    pg_conn: &BlockplotDbConn,
+    token: &str,
+    method: Method,
+) -> request::Outcome<User, ()> {
+    let (api_token, auth_id) = match query_active_api_token(pg_conn, &token_hash(token)) {
+        Ok(Some(found)) => found,
+        Ok(None) => return rocket::Outcome::Failure((Status::Unauthorized, ())),
+        Err(error) => {
+            println!("Error reading api token: {}", error);
+            return rocket::Outcome::Failure((Status::InternalServerError, ()));
+        }
+    };
+
+    let scope: TokenScope = match api_token.scope.parse() {
+        Ok(scope) => scope,
+        Err(_) => return rocket::Outcome::Failure((Status::InternalServerError, ())),
+    };
+    if !scope.allows(method) {
+        return rocket::Outcome::Failure((Status::Forbidden, ()));
+    }
+
+    if let Err(error) = update_api_token_last_used(pg_conn, api_token.id) {
+        println!("Error recording api token use: {}", error);
+    }
+
+    match query_user(pg_conn, auth_id) {
+        Ok(Some(user)) => rocket::Outcome::Success(user),
+        Ok(None) => rocket::Outcome::Failure((Status::InternalServerError, ())),
+        Err(error) => {
+            println!("Error reading user: {}", error);
+            rocket::Outcome::Failure((Status::InternalServerError, ()))
         }
     }
 }
@@ -138,6 +435,94 @@ pub struct NewSkillblock {
     pub offline_category: bool,
     pub skill_name: String,
     pub skill_description: String,
+    pub source: String,
+    pub sync_status: String,
+}
+
+// Struct for recording a fully fetched date range
+#[derive(Insertable)]
+#[table_name = "sync_ranges"]
+pub struct NewSyncRange {
+    pub block_id: i32,
+    pub range_start: NaiveDate,
+    pub range_end: NaiveDate,
+    pub completed_at: NaiveDateTime,
+}
+
+// Struct for creating or replacing goal of a skillblock
+#[derive(Insertable)]
+#[table_name = "goals"]
+pub struct NewGoal {
+    pub block_id: i32,
+    pub period: String,
+    pub target_minutes: i32,
+}
+
+// Struct for querying login session from postgres database.
+// Sessions are looked up by hash of their token, so tokens
+// can't be lifted from the table
+#[derive(Identifiable, Queryable)]
+#[table_name = "sessions"]
+#[primary_key(token_hash)]
+pub struct SessionRecord {
+    pub token_hash: String,
+    pub auth_id: String,
+    pub block_count: i32,
+    pub email: String,
+    pub expires: i64,
+    pub given_name: String,
+    pub key_present: bool,
+    pub nickname: String,
+    pub picture: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Struct for storing login session, replacing
+// any session stored under the same token
+#[derive(AsChangeset, Insertable)]
+#[table_name = "sessions"]
+pub struct NewSession {
+    pub token_hash: String,
+    pub auth_id: String,
+    pub block_count: i32,
+    pub email: String,
+    pub expires: i64,
+    pub given_name: String,
+    pub key_present: bool,
+    pub nickname: String,
+    pub picture: String,
+}
+
+// Struct for querying account deletion confirmation token awaiting
+// use. Only its hash is stored, like session and api tokens
+#[derive(Identifiable, Queryable)]
+#[table_name = "deletion_tokens"]
+#[primary_key(user_id)]
+pub struct DeletionToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires: i64,
+    pub created_at: NaiveDateTime,
+}
+
+// Struct for storing account deletion confirmation token,
+// replacing any token issued to user before
+#[derive(AsChangeset, Insertable)]
+#[table_name = "deletion_tokens"]
+pub struct NewDeletionToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires: i64,
+}
+
+// Struct for storing hash of a newly created api token
+#[derive(Insertable)]
+#[table_name = "api_tokens"]
+pub struct NewApiToken {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scope: String,
 }
 
 // Struct for creating new user record
@@ -1,13 +1,20 @@
 use super::{models, schema};
+use crate::credentials::keyring;
+use crate::sources::TimeSourceKind;
 use anyhow::Result;
 use chrono::Local;
 use chrono::NaiveDateTime;
 use diesel::dsl::exists;
+use diesel::pg::upsert::excluded;
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel::select;
 use rocket_contrib::database;
 
+// Most date_time records written by a single insert statement. Each
+// record binds three parameters, and postgres allows at most 65535
+const UPSERT_CHUNK_SIZE: usize = 10_000;
+
 // Rocket connection pool
 #[database("postgres_blockplot")]
 pub struct BlockplotDbConn(diesel::PgConnection);
@@ -23,12 +30,16 @@ pub fn check_for_skillblock(conn: &PgConnection, cate: String) -> Result<bool, (
     }
 }
 
-// Insert skillblock record into database
-pub fn create_skillblock(connection: &PgConnection, db_struct: models::NewSkillblock) {
-    diesel::insert_into(schema::skillblocks::table)
+// Insert skillblock record into database, returning inserted record
+pub fn create_skillblock(
+    connection: &PgConnection,
+    db_struct: models::NewSkillblock,
+) -> Result<models::Skillblock, diesel::result::Error> {
+    let skillblock_record = diesel::insert_into(schema::skillblocks::table)
         .values(&db_struct)
-        .execute(connection)
-        .expect("Error inserting into database");
+        .get_result(connection);
+
+    skillblock_record
 }
 
 // Insert user record into database
@@ -44,6 +55,16 @@ pub fn create_user(
     Ok(inserted_user)
 }
 
+// Delete user record. Skillblocks of user are removed by cascade,
+// taking their time data, sync ranges and goals with them
+pub fn delete_user(connection: &PgConnection, id: i32) -> Result<usize, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    let result = diesel::delete(users.find(id)).execute(connection);
+
+    result
+}
+
 // Prototype Delete time_date operation. Probably need to change how
 // date_time records are fetched from db
 pub fn delete_date_time(connection: &PgConnection, dt_id: i32) -> usize {
@@ -83,6 +104,32 @@ pub fn query_date_times_desc(
     date_time_records
 }
 
+// Query day_date and day_time of skillblock in descending order,
+// limited to days falling within an optional date range
+pub fn query_date_times_between(
+    connection: &PgConnection,
+    skillblock: &models::Skillblock,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+) -> Result<Vec<(NaiveDateTime, i32)>, diesel::result::Error> {
+    use self::schema::date_times::dsl::*;
+
+    let mut query = models::DateTime::belonging_to(skillblock)
+        .select((day_date, day_time))
+        .order(day_date.desc())
+        .into_boxed();
+    if let Some(start) = from {
+        query = query.filter(day_date.ge(start));
+    }
+    if let Some(end) = to {
+        query = query.filter(day_date.le(end));
+    }
+
+    let date_time_records = query.load::<(NaiveDateTime, i32)>(connection);
+
+    date_time_records
+}
+
 // Query skillblock record from database
 pub fn query_skillblocks(
     connection: &PgConnection,
@@ -94,42 +141,604 @@ pub fn query_skillblocks(
     skillblock_records
 }
 
-// Query user record from database
-pub fn query_user(connection: &PgConnection, id: String) -> Option<models::User> {
+// Query single skillblock record owned by user from database
+pub fn query_owned_skillblock(
+    connection: &PgConnection,
+    user: &models::User,
+    id: i32,
+) -> Result<models::Skillblock, diesel::result::Error> {
+    let skillblock_record = models::Skillblock::belonging_to(user)
+        .find(id)
+        .first::<models::Skillblock>(connection);
+
+    skillblock_record
+}
+
+// Apply changes to skillblock record, returning updated record
+pub fn update_skillblock(
+    connection: &PgConnection,
+    id: i32,
+    changes: &models::SkillblockChanges,
+) -> Result<models::Skillblock, diesel::result::Error> {
+    use self::schema::skillblocks::dsl::*;
+
+    let skillblock_record = diesel::update(skillblocks.find(id))
+        .set(changes)
+        .get_result::<models::Skillblock>(connection);
+
+    skillblock_record
+}
+
+// Query skillblock record shared under a given token
+pub fn query_shared_skillblock(
+    connection: &PgConnection,
+    token: &str,
+) -> Result<models::Skillblock, diesel::result::Error> {
+    use self::schema::skillblocks::dsl::*;
+
+    let skillblock_record = skillblocks
+        .filter(share_token.eq(token))
+        .first::<models::Skillblock>(connection);
+
+    skillblock_record
+}
+
+// Set or clear share token of skillblock, returning updated record
+pub fn update_skillblock_share_token(
+    connection: &PgConnection,
+    id: i32,
+    token: Option<&str>,
+) -> Result<models::Skillblock, diesel::result::Error> {
+    use self::schema::skillblocks::dsl::*;
+
+    let result = diesel::update(skillblocks.find(id))
+        .set(share_token.eq(token))
+        .get_result(connection);
+
+    result
+}
+
+// Delete skillblock record. Time data and sync ranges
+// of skillblock are removed by cascade
+pub fn delete_skillblock(
+    connection: &PgConnection,
+    id: i32,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::skillblocks::dsl::*;
+
+    let result = diesel::delete(skillblocks.find(id)).execute(connection);
+
+    result
+}
+
+// Remove stored time data of skillblock and reset its sync cursor,
+// so time data gets fetched from scratch on next sync. Any backoff
+// is cleared too, since failures no longer apply once it changes
+pub fn reset_skillblock_time_data(
+    connection: &PgConnection,
+    id: i32,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::{date_times, skillblocks, sync_ranges};
+
+    diesel::delete(date_times::table.filter(date_times::block_id.eq(id))).execute(connection)?;
+    diesel::delete(sync_ranges::table.filter(sync_ranges::block_id.eq(id))).execute(connection)?;
+
+    let result = diesel::update(skillblocks::table.find(id))
+        .set((
+            skillblocks::last_synced_at.eq(None::<NaiveDateTime>),
+            skillblocks::sync_status.eq("pending"),
+            skillblocks::sync_failures.eq(0),
+            skillblocks::next_attempt_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(connection);
+
+    result
+}
+
+// Query fully fetched date ranges of skillblock
+pub fn query_sync_ranges(
+    connection: &PgConnection,
+    skillblock: &models::Skillblock,
+) -> Result<Vec<models::SyncRange>, diesel::result::Error> {
+    let sync_range_records =
+        models::SyncRange::belonging_to(skillblock).load::<models::SyncRange>(connection);
+
+    sync_range_records
+}
+
+// Query goal of skillblock, if one is set
+pub fn query_goal(
+    connection: &PgConnection,
+    skillblock: &models::Skillblock,
+) -> Result<Option<models::Goal>, diesel::result::Error> {
+    let goal_record = models::Goal::belonging_to(skillblock)
+        .first::<models::Goal>(connection)
+        .optional();
+
+    goal_record
+}
+
+// Skillblocks have at most one goal, setting a goal replaces any previous one
+pub fn upsert_goal(
+    connection: &PgConnection,
+    goal: &models::NewGoal,
+) -> Result<models::Goal, diesel::result::Error> {
+    use schema::goals::dsl::*;
+
+    let result = diesel::insert_into(goals)
+        .values(goal)
+        .on_conflict(block_id)
+        .do_update()
+        .set((
+            period.eq(excluded(period)),
+            target_minutes.eq(excluded(target_minutes)),
+        ))
+        .get_result(connection);
+
+    result
+}
+
+// Remove goal of skillblock
+pub fn delete_goal(
+    connection: &PgConnection,
+    skillblock: &models::Skillblock,
+) -> Result<usize, diesel::result::Error> {
+    let result = diesel::delete(models::Goal::belonging_to(skillblock)).execute(connection);
+
+    result
+}
+
+// Query login session stored under token hash
+pub fn query_session(
+    connection: &PgConnection,
+    hash: &str,
+) -> Result<Option<models::SessionRecord>, diesel::result::Error> {
+    use self::schema::sessions::dsl::*;
+
+    let session_record = sessions
+        .find(hash)
+        .first::<models::SessionRecord>(connection)
+        .optional();
+
+    session_record
+}
+
+// Store login session, replacing any session stored under the same token hash
+pub fn upsert_session(
+    connection: &PgConnection,
+    session: &models::NewSession,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::sessions::dsl::*;
+
+    let result = diesel::insert_into(sessions)
+        .values(session)
+        .on_conflict(token_hash)
+        .do_update()
+        .set(session)
+        .execute(connection);
+
+    result
+}
+
+// Delete login session stored under token hash
+pub fn delete_session(
+    connection: &PgConnection,
+    hash: &str,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::sessions::dsl::*;
+
+    let result = diesel::delete(sessions.find(hash)).execute(connection);
+
+    result
+}
+
+// Delete every login session of user
+pub fn delete_user_sessions(
+    connection: &PgConnection,
+    id: &str,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::sessions::dsl::*;
+
+    let result = diesel::delete(sessions.filter(auth_id.eq(id))).execute(connection);
+
+    result
+}
+
+// Delete login sessions that expired at or before a unix timestamp
+pub fn delete_expired_sessions(
+    connection: &PgConnection,
+    now: i64,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::sessions::dsl::*;
+
+    let result = diesel::delete(sessions.filter(expires.le(now))).execute(connection);
+
+    result
+}
+
+// Store account deletion confirmation token of user,
+// replacing any token issued to them before
+pub fn upsert_deletion_token(
+    connection: &PgConnection,
+    token: &models::NewDeletionToken,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::deletion_tokens::dsl::*;
+
+    let result = diesel::insert_into(deletion_tokens)
+        .values(token)
+        .on_conflict(user_id)
+        .do_update()
+        .set(token)
+        .execute(connection);
+
+    result
+}
+
+// Remove account deletion confirmation token of user,
+// returning removed token if one was issued
+pub fn take_deletion_token(
+    connection: &PgConnection,
+    id: i32,
+) -> Result<Option<models::DeletionToken>, diesel::result::Error> {
+    use self::schema::deletion_tokens::dsl::*;
+
+    let token_record = diesel::delete(deletion_tokens.find(id))
+        .get_result::<models::DeletionToken>(connection)
+        .optional();
+
+    token_record
+}
+
+// Delete account deletion confirmation tokens that
+// expired at or before a unix timestamp
+pub fn delete_expired_deletion_tokens(
+    connection: &PgConnection,
+    now: i64,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::deletion_tokens::dsl::*;
+
+    let result = diesel::delete(deletion_tokens.filter(expires.le(now))).execute(connection);
+
+    result
+}
+
+// Insert hash of a new api token, returning inserted record
+pub fn insert_api_token(
+    connection: &PgConnection,
+    token: &models::NewApiToken,
+) -> Result<models::ApiToken, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let result = diesel::insert_into(api_tokens)
+        .values(token)
+        .get_result(connection);
+
+    result
+}
+
+// Query api tokens of user, revoked ones included, oldest first
+pub fn query_api_tokens(
+    connection: &PgConnection,
+    user: &models::User,
+) -> Result<Vec<models::ApiToken>, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let token_records = models::ApiToken::belonging_to(user)
+        .order(id.asc())
+        .load::<models::ApiToken>(connection);
+
+    token_records
+}
+
+// Query single api token of user, revoked or not
+pub fn query_api_token(
+    connection: &PgConnection,
+    user: &models::User,
+    token_id: i32,
+) -> Result<Option<models::ApiToken>, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let token_record = models::ApiToken::belonging_to(user)
+        .filter(id.eq(token_id))
+        .first::<models::ApiToken>(connection)
+        .optional();
+
+    token_record
+}
+
+// Query unrevoked api token stored under token hash, along
+// with auth id of the user it belongs to
+pub fn query_active_api_token(
+    connection: &PgConnection,
+    hash: &str,
+) -> Result<Option<(models::ApiToken, String)>, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+    use self::schema::users;
+
+    let token_record = api_tokens
+        .inner_join(users::table)
+        .filter(token_hash.eq(hash))
+        .filter(revoked_at.is_null())
+        .select((schema::api_tokens::all_columns, users::auth_id))
+        .first::<(models::ApiToken, String)>(connection)
+        .optional();
+
+    token_record
+}
+
+// Count unrevoked api tokens of user
+pub fn count_active_api_tokens(
+    connection: &PgConnection,
+    user: &models::User,
+) -> Result<i64, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let result = models::ApiToken::belonging_to(user)
+        .filter(revoked_at.is_null())
+        .count()
+        .get_result(connection);
+
+    result
+}
+
+// Revoke api token of user, unless already revoked. Returns
+// number of tokens revoked, zero when token isn't found
+pub fn update_api_token_revoked(
+    connection: &PgConnection,
+    user: &models::User,
+    token_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let current_timestamp = Local::now().naive_utc();
+    let result = diesel::update(
+        models::ApiToken::belonging_to(user)
+            .filter(id.eq(token_id))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(current_timestamp))
+    .execute(connection);
+
+    result
+}
+
+// Record api token being used to authenticate a request
+pub fn update_api_token_last_used(
+    connection: &PgConnection,
+    token_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::api_tokens::dsl::*;
+
+    let current_timestamp = Local::now().naive_utc();
+    let result = diesel::update(api_tokens.find(token_id))
+        .set(last_used_at.eq(current_timestamp))
+        .execute(connection);
+
+    result
+}
+
+// Query user record from database. Credentials that fail to open
+// are an error rather than a missing user, so callers never mistake
+// an existing user for a new one
+pub fn query_user(
+    connection: &PgConnection,
+    id: String,
+) -> Result<Option<models::User>, diesel::result::Error> {
     use self::schema::users::dsl::*;
 
     let user = users
         .filter(auth_id.eq(id))
+        .first::<models::User>(connection)
+        .optional()?;
+
+    user.map(open_credentials).transpose()
+}
+
+// Query every user holding credentials, without opening them
+pub fn query_users_with_credentials(
+    connection: &PgConnection,
+) -> Result<Vec<models::User>, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    let user_records = users
+        .filter(
+            api_key
+                .is_not_null()
+                .or(toggl_api_key.is_not_null())
+                .or(wakatime_api_key.is_not_null()),
+        )
+        .order(user_id)
+        .load::<models::User>(connection);
+
+    user_records
+}
+
+// Query user record by id and lock it until end of transaction,
+// without opening credentials
+pub fn lock_user(
+    connection: &PgConnection,
+    id: i32,
+) -> Result<models::User, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    let user_record = users
+        .find(id)
+        .for_update()
         .first::<models::User>(connection);
 
-    match user {
-        Ok(record) => Some(record),
-        Err(_) => None,
+    user_record
+}
+
+// Store sealed credentials of user
+pub fn update_user_credentials(
+    connection: &PgConnection,
+    id: i32,
+    changes: &models::CredentialChanges,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    let result = diesel::update(users.find(id))
+        .set(changes)
+        .execute(connection);
+
+    result
+}
+
+// Open sealed credentials of user record. Rows without a data key
+// still hold plaintext, and are returned without touching keyring
+fn open_credentials(user: models::User) -> Result<models::User, diesel::result::Error> {
+    if user.credentials_key_id.is_none() || user.wrapped_data_key.is_none() {
+        return Ok(user);
     }
+
+    keyring()
+        .and_then(|keyring| keyring.open_credentials(user))
+        .map_err(|error| diesel::result::Error::DeserializationError(error.into()))
 }
 
-// Prototype add date_time query
-pub fn add_date_time(
+// Change credentials of user, then seal all of them under a fresh
+// data key. Row is locked so concurrent changes aren't lost
+fn store_credentials<F: FnOnce(&mut models::User)>(
     connection: &PgConnection,
-    date_time: models::NewDateTime,
+    id: &str,
+    change: F,
 ) -> Result<usize, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    connection.transaction(|| {
+        let record = users
+            .filter(auth_id.eq(id))
+            .for_update()
+            .first::<models::User>(connection)?;
+        let mut record = open_credentials(record)?;
+        change(&mut record);
+
+        let changes = keyring()
+            .and_then(|keyring| keyring.seal_credentials(&record))
+            .map_err(|error| diesel::result::Error::SerializationError(error.into()))?;
+
+        update_user_credentials(connection, record.user_id, &changes)
+    })
+}
+
+// Query skillblocks backed by an external time source that haven't
+// been synced since a given timestamp, along with their owners.
+// Skillblocks backing off after failed syncs are left out until
+// their next attempt is due. Credentials are opened row by row, so
+// an owner whose credentials fail to open doesn't hold up the others
+pub fn query_skillblocks_due_for_sync(
+    connection: &PgConnection,
+    synced_before: NaiveDateTime,
+    now: NaiveDateTime,
+) -> Result<
+    Vec<(
+        models::Skillblock,
+        Result<models::User, diesel::result::Error>,
+    )>,
+    diesel::result::Error,
+> {
+    use self::schema::skillblocks::dsl::*;
+
+    let skillblock_records = skillblocks
+        .inner_join(schema::users::table)
+        .filter(source.ne(TimeSourceKind::Manual.as_str()))
+        .filter(
+            last_synced_at
+                .is_null()
+                .or(last_synced_at.lt(synced_before)),
+        )
+        .filter(next_attempt_at.is_null().or(next_attempt_at.le(now)))
+        .load::<(models::Skillblock, models::User)>(connection)?;
+
+    let due = skillblock_records
+        .into_iter()
+        .map(|(skillblock, user)| (skillblock, open_credentials(user)))
+        .collect();
+
+    Ok(due)
+}
+
+// Query date_time record of skillblock for a given day
+pub fn query_date_time(
+    connection: &PgConnection,
+    fk_id: i32,
+    date: NaiveDateTime,
+) -> Result<models::DateTime, diesel::result::Error> {
+    use self::schema::date_times::dsl::*;
+
+    let date_time_record = date_times
+        .filter(block_id.eq(fk_id))
+        .filter(day_date.eq(date))
+        .first::<models::DateTime>(connection);
+
+    date_time_record
+}
+
+// Insert date_time record into database, returning inserted record.
+// Unlike upsert_date_times, fails on a day already on record, for
+// writes that must not overwrite existing time data
+pub fn create_date_time(
+    connection: &PgConnection,
+    date_time: models::NewDateTime,
+) -> Result<models::DateTime, diesel::result::Error> {
     let result = diesel::insert_into(schema::date_times::table)
         .values(&date_time)
-        .execute(connection)
-        .expect("Error inserting date_time into database");
+        .get_result(connection);
 
-    Ok(result)
+    result
 }
 
-// Prototype function adds whole vector of date times in a single query
-pub fn batch_add_date_times(
+// Delete date_time record of skillblock for a given day
+pub fn delete_date_time_by_day(
     connection: &PgConnection,
-    date_data: &Vec<models::NewDateTime>,
+    fk_id: i32,
+    date: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::date_times::dsl::*;
+
+    let target = date_times
+        .filter(block_id.eq(fk_id))
+        .filter(day_date.eq(date));
+
+    let result = diesel::delete(target).execute(connection);
+
+    result
+}
+
+// Insert date_time records, overwriting time of any skillblock day
+// already on record. Relies on unique index over (block_id, day_date).
+// Records are written in chunks inside a transaction, keeping each
+// statement under the bind parameter limit of postgres
+pub fn upsert_date_times(
+    connection: &PgConnection,
+    date_data: &[models::NewDateTime],
 ) -> Result<usize, Error> {
     use schema::date_times::dsl::*;
 
-    let result = diesel::insert_into(date_times)
-        .values(date_data)
+    let result = connection.transaction(|| {
+        let mut upserted = 0;
+        for chunk in date_data.chunks(UPSERT_CHUNK_SIZE) {
+            upserted += diesel::insert_into(date_times)
+                .values(chunk)
+                .on_conflict((block_id, day_date))
+                .do_update()
+                .set(day_time.eq(excluded(day_time)))
+                .execute(connection)?;
+        }
+
+        Ok(upserted)
+    });
+
+    result
+}
+
+// Record date range of skillblock as fully fetched
+pub fn add_sync_range(
+    connection: &PgConnection,
+    sync_range: models::NewSyncRange,
+) -> Result<usize, diesel::result::Error> {
+    let result = diesel::insert_into(schema::sync_ranges::table)
+        .values(&sync_range)
         .execute(connection);
 
     result
@@ -143,16 +752,43 @@ pub fn add_user_key(
 ) -> Result<(usize, usize), diesel::result::Error> {
     use self::schema::users::dsl::*;
 
-    let target = users.filter(auth_id.eq(&id));
-    let key_result = diesel::update(target)
-        .set(api_key.eq(key))
-        .execute(connection)?;
-    let target = users.filter(auth_id.eq(id));
-    let bool_result = diesel::update(target)
-        .set(key_present.eq(true))
-        .execute(connection)?;
+    // Key and its flag change together, or not at all
+    connection.transaction(|| {
+        let key_result =
+            store_credentials(connection, &id, |user| user.api_key = Some(key.clone()))?;
+        let target = users.filter(auth_id.eq(id));
+        let bool_result = diesel::update(target)
+            .set(key_present.eq(true))
+            .execute(connection)?;
+
+        Ok((key_result, bool_result))
+    })
+}
+
+// Store Toggl api token on user record
+pub fn add_user_toggl_key(
+    connection: &PgConnection,
+    id: String,
+    key: &String,
+) -> Result<usize, diesel::result::Error> {
+    let result = store_credentials(connection, &id, |user| {
+        user.toggl_api_key = Some(key.clone())
+    })?;
+
+    Ok(result)
+}
+
+// Store WakaTime api key on user record
+pub fn add_user_wakatime_key(
+    connection: &PgConnection,
+    id: String,
+    key: &String,
+) -> Result<usize, diesel::result::Error> {
+    let result = store_credentials(connection, &id, |user| {
+        user.wakatime_api_key = Some(key.clone())
+    })?;
 
-    Ok((key_result, bool_result))
+    Ok(result)
 }
 
 // Prototype block_count update query
@@ -172,6 +808,25 @@ pub fn update_block_count(
     Ok(result)
 }
 
+// Set user block count to number of skillblocks
+// the user owns, returning the new count
+pub fn refresh_block_count(
+    connection: &PgConnection,
+    user: &models::User,
+) -> Result<i32, diesel::result::Error> {
+    use self::schema::users::dsl::*;
+
+    let owned: i64 = models::Skillblock::belonging_to(user)
+        .count()
+        .get_result(connection)?;
+
+    diesel::update(users.find(user.user_id))
+        .set(block_count.eq(owned as i32))
+        .execute(connection)?;
+
+    Ok(owned as i32)
+}
+
 // Prototype date_time update query
 pub fn update_date_time(
     connection: &PgConnection,
@@ -192,6 +847,37 @@ pub fn update_date_time(
     result
 }
 
+// Update sync status of skillblock. Sync cursor is only moved
+// forward when a timestamp is given. Failure count and time of next
+// attempt are always written, clearing any backoff on success
+pub fn update_skillblock_sync(
+    connection: &PgConnection,
+    id: i32,
+    status: &str,
+    synced_at: Option<NaiveDateTime>,
+    failures: i32,
+    next_attempt: Option<NaiveDateTime>,
+) -> Result<usize, diesel::result::Error> {
+    use self::schema::skillblocks::dsl::*;
+
+    let target = skillblocks.find(id);
+    let backoff = (sync_failures.eq(failures), next_attempt_at.eq(next_attempt));
+    let result = match synced_at {
+        Some(timestamp) => diesel::update(target)
+            .set((
+                sync_status.eq(status),
+                last_synced_at.eq(timestamp),
+                backoff,
+            ))
+            .execute(connection),
+        None => diesel::update(target)
+            .set((sync_status.eq(status), backoff))
+            .execute(connection),
+    };
+
+    result
+}
+
 // Update database record that keeps track of
 // last date skillblocks were fetched
 pub fn update_blocks_last_fetched(
@@ -1,3 +1,16 @@
+table! {
+    api_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        name -> Varchar,
+        token_hash -> Varchar,
+        scope -> Varchar,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     date_times (id) {
         id -> Int4,
@@ -7,6 +20,40 @@ table! {
     }
 }
 
+table! {
+    deletion_tokens (user_id) {
+        user_id -> Int4,
+        token_hash -> Varchar,
+        expires -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    goals (id) {
+        id -> Int4,
+        block_id -> Int4,
+        period -> Varchar,
+        target_minutes -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    sessions (token_hash) {
+        token_hash -> Varchar,
+        auth_id -> Varchar,
+        block_count -> Int4,
+        email -> Varchar,
+        expires -> Int8,
+        given_name -> Varchar,
+        key_present -> Bool,
+        nickname -> Varchar,
+        picture -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     skillblocks (block_id) {
         block_id -> Int4,
@@ -15,6 +62,25 @@ table! {
         offline_category -> Bool,
         skill_name -> Varchar,
         skill_description -> Varchar,
+        source -> Varchar,
+        last_synced_at -> Nullable<Timestamp>,
+        sync_status -> Varchar,
+        palette -> Varchar,
+        intensity_scale -> Varchar,
+        thresholds -> Array<Int4>,
+        share_token -> Nullable<Varchar>,
+        sync_failures -> Int4,
+        next_attempt_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    sync_ranges (id) {
+        id -> Int4,
+        block_id -> Int4,
+        range_start -> Date,
+        range_end -> Date,
+        completed_at -> Timestamp,
     }
 }
 
@@ -28,10 +94,27 @@ table! {
         created_at -> Timestamp,
         blocks_last_fetched -> Timestamp,
         last_login -> Timestamp,
+        toggl_api_key -> Nullable<Varchar>,
+        wakatime_api_key -> Nullable<Varchar>,
+        credentials_key_id -> Nullable<Varchar>,
+        wrapped_data_key -> Nullable<Varchar>,
     }
 }
 
+joinable!(api_tokens -> users (user_id));
 joinable!(date_times -> skillblocks (block_id));
+joinable!(deletion_tokens -> users (user_id));
+joinable!(goals -> skillblocks (block_id));
 joinable!(skillblocks -> users (user_id));
+joinable!(sync_ranges -> skillblocks (block_id));
 
-allow_tables_to_appear_in_same_query!(date_times, skillblocks, users,);
+allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    date_times,
+    deletion_tokens,
+    goals,
+    sessions,
+    skillblocks,
+    sync_ranges,
+    users,
+);
@@ -0,0 +1,132 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket_contrib::json::Json;
+
+use serde::Serialize;
+
+// Body of every error response sent by the api. Code is a stable,
+// machine readable identifier that clients can match on. Details
+// carry any extra context, such as per field validation errors
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+// Error returned by api route handlers. Responds with
+// its status and a json encoded ErrorBody
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub body: ErrorBody,
+}
+
+impl ApiError {
+    pub fn new(status: Status, code: &str, message: &str) -> Self {
+        ApiError {
+            status,
+            body: ErrorBody {
+                code: code.to_string(),
+                message: message.to_string(),
+                details: None,
+            },
+        }
+    }
+
+    // Attach extra context to error
+    pub fn with_details<T: Serialize>(mut self, details: T) -> Self {
+        self.body.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    pub fn bad_request(code: &str, message: &str) -> Self {
+        ApiError::new(Status::BadRequest, code, message)
+    }
+
+    pub fn unauthorized() -> Self {
+        ApiError::new(Status::Unauthorized, "unauthorized", "Login required")
+    }
+
+    pub fn forbidden(code: &str, message: &str) -> Self {
+        ApiError::new(Status::Forbidden, code, message)
+    }
+
+    pub fn not_found(code: &str, message: &str) -> Self {
+        ApiError::new(Status::NotFound, code, message)
+    }
+
+    pub fn conflict(code: &str, message: &str) -> Self {
+        ApiError::new(Status::Conflict, code, message)
+    }
+
+    pub fn unprocessable(code: &str, message: &str) -> Self {
+        ApiError::new(Status::UnprocessableEntity, code, message)
+    }
+
+    pub fn internal() -> Self {
+        ApiError::new(
+            Status::InternalServerError,
+            "internal_error",
+            "Something went wrong on our end",
+        )
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        Response::build_from(Json(self.body).respond_to(request)?)
+            .status(self.status)
+            .ok()
+    }
+}
+
+// Records missing from database are reported as not found,
+// every other database error is logged and hidden from client
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => {
+                ApiError::not_found("not_found", "Resource not found")
+            }
+            _ => {
+                println!("Database error: {}", error);
+                ApiError::internal()
+            }
+        }
+    }
+}
+
+// Catchers give errors raised outside of route handlers, such
+// as failed request guards, the same json body as api errors
+#[catch(400)]
+pub fn bad_request() -> ApiError {
+    ApiError::bad_request("bad_request", "Request couldn't be understood")
+}
+
+#[catch(401)]
+pub fn unauthorized() -> ApiError {
+    ApiError::unauthorized()
+}
+
+#[catch(403)]
+pub fn forbidden() -> ApiError {
+    ApiError::forbidden("forbidden", "Access to resource denied")
+}
+
+#[catch(404)]
+pub fn not_found() -> ApiError {
+    ApiError::not_found("not_found", "Resource not found")
+}
+
+#[catch(422)]
+pub fn unprocessable_entity() -> ApiError {
+    ApiError::unprocessable("invalid_body", "Request body couldn't be processed")
+}
+
+#[catch(500)]
+pub fn internal_error() -> ApiError {
+    ApiError::internal()
+}
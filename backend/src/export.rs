@@ -0,0 +1,286 @@
+use crate::db::models::{DateTime, Skillblock, User};
+
+use anyhow::{anyhow, Error};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use serde::Serialize;
+
+use std::io::{Cursor, Seek, Write};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// Profile row of user. Time source credentials are secrets rather than
+// user data, so only whether each one is stored is exported
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportProfile {
+    pub user_id: i32,
+    pub auth_id: String,
+    pub block_count: i32,
+    pub created_at: NaiveDateTime,
+    pub last_login: NaiveDateTime,
+    pub blocks_last_fetched: NaiveDateTime,
+    pub rescuetime_key_stored: bool,
+    pub toggl_key_stored: bool,
+    pub wakatime_key_stored: bool,
+}
+
+impl From<&User> for ExportProfile {
+    fn from(user: &User) -> Self {
+        ExportProfile {
+            user_id: user.user_id,
+            auth_id: user.auth_id.clone(),
+            block_count: user.block_count,
+            created_at: user.created_at,
+            last_login: user.last_login,
+            blocks_last_fetched: user.blocks_last_fetched,
+            rescuetime_key_stored: user.api_key.is_some(),
+            toggl_key_stored: user.toggl_api_key.is_some(),
+            wakatime_key_stored: user.wakatime_api_key.is_some(),
+        }
+    }
+}
+
+// Skillblock row flattened so it fits a csv file. Thresholds are
+// joined by semicolons and share token is left out, like credentials
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportSkillblock {
+    pub block_id: i32,
+    pub category: String,
+    pub offline_category: bool,
+    pub skill_name: String,
+    pub description: String,
+    pub source: String,
+    pub sync_status: String,
+    pub last_synced_at: Option<NaiveDateTime>,
+    pub palette: String,
+    pub intensity_scale: String,
+    pub thresholds: String,
+    pub shared: bool,
+}
+
+impl From<&Skillblock> for ExportSkillblock {
+    fn from(skillblock: &Skillblock) -> Self {
+        let thresholds: Vec<String> = skillblock
+            .thresholds
+            .iter()
+            .map(|threshold| threshold.to_string())
+            .collect();
+
+        ExportSkillblock {
+            block_id: skillblock.block_id,
+            category: skillblock.category.clone(),
+            offline_category: skillblock.offline_category,
+            skill_name: skillblock.skill_name.clone(),
+            description: skillblock.description.clone(),
+            source: skillblock.source.clone(),
+            sync_status: skillblock.sync_status.clone(),
+            last_synced_at: skillblock.last_synced_at,
+            palette: skillblock.palette.clone(),
+            intensity_scale: skillblock.intensity_scale.clone(),
+            thresholds: thresholds.join(";"),
+            shared: skillblock.share_token.is_some(),
+        }
+    }
+}
+
+// Time data row. Day time is in seconds, like the date_times table
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportDateTime {
+    pub id: i32,
+    pub block_id: Option<i32>,
+    pub day_date: NaiveDate,
+    pub day_time: i32,
+}
+
+impl From<&DateTime> for ExportDateTime {
+    fn from(date_time: &DateTime) -> Self {
+        ExportDateTime {
+            id: date_time.id,
+            block_id: date_time.block_id,
+            day_date: date_time.day_date.date(),
+            day_time: date_time.day_time,
+        }
+    }
+}
+
+// Build zip archive holding profile, skillblocks and time data of
+// a user, each as both a json and a csv file
+pub fn build_archive(
+    user: &User,
+    skillblocks: &[Skillblock],
+    date_times: &[DateTime],
+) -> Result<Vec<u8>, Error> {
+    let profile = ExportProfile::from(user);
+    let skillblocks: Vec<ExportSkillblock> = skillblocks.iter().map(From::from).collect();
+    let date_times: Vec<ExportDateTime> = date_times.iter().map(From::from).collect();
+
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    add_json(&mut archive, "profile.json", &profile)?;
+    add_csv(&mut archive, "profile.csv", &[profile])?;
+    add_json(&mut archive, "skillblocks.json", &skillblocks)?;
+    add_csv(&mut archive, "skillblocks.csv", &skillblocks)?;
+    add_json(&mut archive, "date_times.json", &date_times)?;
+    add_csv(&mut archive, "date_times.csv", &date_times)?;
+
+    Ok(archive.finish()?.into_inner())
+}
+
+fn file_options() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+fn add_json<W: Write + Seek, T: Serialize>(
+    archive: &mut ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> Result<(), Error> {
+    archive.start_file(name, file_options())?;
+    serde_json::to_writer_pretty(archive, value)?;
+
+    Ok(())
+}
+
+// Tables without any rows are written as empty files
+fn add_csv<W: Write + Seek, T: Serialize>(
+    archive: &mut ZipWriter<W>,
+    name: &str,
+    rows: &[T],
+) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let csv = writer
+        .into_inner()
+        .map_err(|error| anyhow!("Error writing {}: {}", name, error))?;
+
+    archive.start_file(name, file_options())?;
+    archive.write_all(&csv)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    fn timestamp(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn user() -> User {
+        User {
+            user_id: 7,
+            auth_id: String::from("auth0|test"),
+            api_key: Some(String::from("secret-rescuetime-key")),
+            key_present: true,
+            block_count: 1,
+            blocks_last_fetched: timestamp(2021, 8, 1),
+            created_at: timestamp(2021, 1, 1),
+            last_login: timestamp(2021, 8, 1),
+            toggl_api_key: None,
+            wakatime_api_key: None,
+            credentials_key_id: None,
+            wrapped_data_key: None,
+        }
+    }
+
+    fn skillblock() -> Skillblock {
+        Skillblock {
+            block_id: 3,
+            user_id: Some(7),
+            category: String::from("software development"),
+            offline_category: false,
+            skill_name: String::from("Programming"),
+            description: String::from("Writing, reading, \"reviewing\" code"),
+            source: String::from("rescuetime"),
+            last_synced_at: None,
+            sync_status: String::from("pending"),
+            palette: String::from("classic"),
+            intensity_scale: String::from("fixed"),
+            thresholds: vec![15, 30, 45, 60],
+            share_token: Some(String::from("token")),
+            sync_failures: 0,
+            next_attempt_at: None,
+        }
+    }
+
+    fn read_file(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut contents = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        contents
+    }
+
+    #[test]
+    fn archive_holds_every_table_as_json_and_csv() {
+        let date_times = vec![DateTime {
+            id: 11,
+            block_id: Some(3),
+            day_time: 1800,
+            day_date: timestamp(2021, 2, 9),
+        }];
+
+        let bytes = build_archive(&user(), &[skillblock()], &date_times).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "date_times.csv",
+                "date_times.json",
+                "profile.csv",
+                "profile.json",
+                "skillblocks.csv",
+                "skillblocks.json",
+            ]
+        );
+
+        assert_eq!(
+            read_file(&mut archive, "date_times.csv"),
+            "id,block_id,day_date,day_time\n11,3,2021-02-09,1800\n"
+        );
+        let exported: Vec<ExportSkillblock> =
+            serde_json::from_str(&read_file(&mut archive, "skillblocks.json")).unwrap();
+        assert_eq!(exported[0].thresholds, "15;30;45;60");
+        assert!(exported[0].shared);
+    }
+
+    #[test]
+    fn credentials_and_share_tokens_are_left_out() {
+        let bytes = build_archive(&user(), &[skillblock()], &[]).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let profile: ExportProfile =
+            serde_json::from_str(&read_file(&mut archive, "profile.json")).unwrap();
+        assert!(profile.rescuetime_key_stored);
+        assert!(!profile.toggl_key_stored);
+
+        for name in &[
+            "profile.json",
+            "profile.csv",
+            "skillblocks.json",
+            "skillblocks.csv",
+        ] {
+            let contents = read_file(&mut archive, name);
+            assert!(!contents.contains("secret-rescuetime-key"));
+            assert!(!contents.contains("token"));
+        }
+        assert_eq!(read_file(&mut archive, "date_times.csv"), "");
+    }
+}
@@ -0,0 +1,213 @@
+use crate::db::models::Goal;
+use crate::import::MINUTES_PER_DAY;
+use crate::stats::week_start;
+
+use anyhow::{anyhow, Error};
+
+use chrono::{Duration, NaiveDate};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Length of time a goal target has to be reached within
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GoalPeriod {
+    Daily,
+    Weekly,
+}
+
+impl GoalPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalPeriod::Daily => "daily",
+            GoalPeriod::Weekly => "weekly",
+        }
+    }
+
+    // Most minutes a period can hold. Larger targets could never be met
+    pub fn max_minutes(&self) -> i32 {
+        match self {
+            GoalPeriod::Daily => MINUTES_PER_DAY,
+            GoalPeriod::Weekly => MINUTES_PER_DAY * 7,
+        }
+    }
+
+    // First day of period containing given day. Weekly
+    // periods start on sunday, matching the block grid
+    pub fn start_of(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Daily => day,
+            GoalPeriod::Weekly => week_start(day),
+        }
+    }
+
+    // Last day of period starting on given day
+    pub fn end_of(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Daily => start,
+            GoalPeriod::Weekly => start + Duration::days(6),
+        }
+    }
+}
+
+impl FromStr for GoalPeriod {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "daily" => Ok(GoalPeriod::Daily),
+            "weekly" => Ok(GoalPeriod::Weekly),
+            _ => Err(anyhow!("Unknown goal period: {}", value)),
+        }
+    }
+}
+
+// Whether the goal target was reached within a single period.
+// Status is "met", "missed", or "in_progress" for an unmet period
+// that hasn't ended yet
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PeriodProgress {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub seconds: i32,
+    pub status: String,
+}
+
+// Goal of a skillblock along with its progress over a date range
+#[derive(Deserialize, Serialize)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub met: i32,
+    pub missed: i32,
+    pub periods: Vec<PeriodProgress>,
+}
+
+// Evaluate daily time totals against a goal target for every period
+// overlapping start to end. Periods starting after today are left out
+pub fn evaluate_goal(
+    date_times: &[(NaiveDate, i32)],
+    period: GoalPeriod,
+    target_minutes: i32,
+    start: NaiveDate,
+    end: NaiveDate,
+    today: NaiveDate,
+) -> Vec<PeriodProgress> {
+    let mut totals: HashMap<NaiveDate, i32> = HashMap::new();
+    for (day_date, day_time) in date_times {
+        *totals.entry(period.start_of(*day_date)).or_insert(0) += day_time;
+    }
+
+    let last = std::cmp::min(end, today);
+    let mut periods = Vec::new();
+    let mut period_start = period.start_of(start);
+
+    while period_start <= last {
+        let period_end = period.end_of(period_start);
+        let seconds = totals.get(&period_start).cloned().unwrap_or(0);
+        let status = if seconds >= target_minutes * 60 {
+            "met"
+        } else if period_end >= today {
+            "in_progress"
+        } else {
+            "missed"
+        };
+
+        periods.push(PeriodProgress {
+            start: period_start,
+            end: period_end,
+            seconds,
+            status: status.to_string(),
+        });
+        period_start = period_end + Duration::days(1);
+    }
+
+    periods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    // Fixture of date_times rows spread over two weeks of february 2021.
+    // February 7th and 14th 2021 were sundays
+    fn fixture() -> Vec<(NaiveDate, i32)> {
+        vec![
+            (date(2021, 2, 8), 1800),
+            (date(2021, 2, 9), 900),
+            (date(2021, 2, 10), 3600),
+            (date(2021, 2, 12), 1800),
+            (date(2021, 2, 15), 600),
+        ]
+    }
+
+    fn statuses(periods: &[PeriodProgress]) -> Vec<&str> {
+        periods
+            .iter()
+            .map(|period| period.status.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn daily_goal_is_met_on_days_reaching_target() {
+        let periods = evaluate_goal(
+            &fixture(),
+            GoalPeriod::Daily,
+            30,
+            date(2021, 2, 8),
+            date(2021, 2, 12),
+            date(2021, 3, 1),
+        );
+
+        assert_eq!(
+            statuses(&periods),
+            vec!["met", "missed", "met", "missed", "met"]
+        );
+        assert_eq!(periods[1].seconds, 900);
+        assert_eq!(periods[3].seconds, 0);
+    }
+
+    #[test]
+    fn weekly_goal_sums_days_of_sunday_week() {
+        let periods = evaluate_goal(
+            &fixture(),
+            GoalPeriod::Weekly,
+            120,
+            date(2021, 2, 10),
+            date(2021, 2, 20),
+            date(2021, 3, 1),
+        );
+
+        assert_eq!(periods.len(), 2);
+        assert_eq!(periods[0].start, date(2021, 2, 7));
+        assert_eq!(periods[0].end, date(2021, 2, 13));
+        assert_eq!(periods[0].seconds, 8100);
+        assert_eq!(statuses(&periods), vec!["met", "missed"]);
+    }
+
+    #[test]
+    fn unmet_current_period_is_in_progress() {
+        let periods = evaluate_goal(
+            &fixture(),
+            GoalPeriod::Weekly,
+            120,
+            date(2021, 2, 7),
+            date(2021, 2, 28),
+            date(2021, 2, 16),
+        );
+
+        // Weeks starting after today are left out
+        assert_eq!(statuses(&periods), vec!["met", "in_progress"]);
+    }
+
+    #[test]
+    fn period_strings_round_trip() {
+        for period in &[GoalPeriod::Daily, GoalPeriod::Weekly] {
+            assert_eq!(period.as_str().parse::<GoalPeriod>().unwrap(), *period);
+        }
+        assert!("monthly".parse::<GoalPeriod>().is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::{anyhow, Error};
+
+use chrono::NaiveDate;
+
+use rocket::http::RawStr;
+use rocket::request::FromFormValue;
+
+use std::collections::HashMap;
+
+// Longest amount of time that can be logged for a single day
+pub const MINUTES_PER_DAY: i32 = 1440;
+
+// How rows landing on a day that already has time data are handled.
+// Applies to days already on record as well as days repeated
+// within the imported file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    Reject,
+    Replace,
+    Sum,
+}
+
+impl<'v> FromFormValue<'v> for DuplicatePolicy {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        match form_value.as_str() {
+            "reject" => Ok(DuplicatePolicy::Reject),
+            "replace" => Ok(DuplicatePolicy::Replace),
+            "sum" => Ok(DuplicatePolicy::Sum),
+            _ => Err(form_value),
+        }
+    }
+}
+
+// Single day of imported time data
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ImportRow {
+    pub date: NaiveDate,
+    pub minutes: i32,
+}
+
+// Error tied to a row of imported data. Rows are numbered from 1
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+// Summary of import returned to client
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub errors: Vec<RowError>,
+}
+
+// Database writes needed to apply an import. Time data
+// is in seconds, matching the date_times table
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportPlan {
+    pub inserts: Vec<(NaiveDate, i32)>,
+    pub updates: Vec<(NaiveDate, i32)>,
+    pub errors: Vec<RowError>,
+}
+
+fn row_error(row: usize, message: String) -> RowError {
+    RowError { row, message }
+}
+
+// Parse csv data with "date,minutes" records. A leading header row is skipped
+pub fn parse_csv(body: &str) -> (Vec<(usize, ImportRow)>, Vec<RowError>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let row = index + 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                errors.push(row_error(row, error.to_string()));
+                continue;
+            }
+        };
+
+        if row == 1
+            && record
+                .get(0)
+                .map_or(false, |field| field.eq_ignore_ascii_case("date"))
+        {
+            continue;
+        }
+
+        if record.len() != 2 {
+            errors.push(row_error(
+                row,
+                format!("Expected 2 fields, found {}", record.len()),
+            ));
+            continue;
+        }
+
+        let date = match NaiveDate::parse_from_str(&record[0], "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                errors.push(row_error(row, format!("Invalid date: {}", &record[0])));
+                continue;
+            }
+        };
+        let minutes = match record[1].parse::<i32>() {
+            Ok(minutes) => minutes,
+            Err(_) => {
+                errors.push(row_error(row, format!("Invalid minutes: {}", &record[1])));
+                continue;
+            }
+        };
+
+        rows.push((row, ImportRow { date, minutes }));
+    }
+
+    (rows, errors)
+}
+
+// Parse json array of {"date": "YYYY-MM-DD", "minutes": 30} objects.
+// Fails outright only if body isn't a json array
+pub fn parse_json(body: &str) -> Result<(Vec<(usize, ImportRow)>, Vec<RowError>), Error> {
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(body).map_err(|error| anyhow!("Invalid json array: {}", error))?;
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        let row = index + 1;
+        match serde_json::from_value::<ImportRow>(value) {
+            Ok(import_row) => rows.push((row, import_row)),
+            Err(error) => errors.push(row_error(row, error.to_string())),
+        }
+    }
+
+    Ok((rows, errors))
+}
+
+// Validate parsed rows and work out which days need inserting or updating,
+// given seconds already on record for each day
+pub fn plan_import(
+    rows: Vec<(usize, ImportRow)>,
+    existing: &HashMap<NaiveDate, i32>,
+    policy: DuplicatePolicy,
+    today: NaiveDate,
+) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+
+    // Seconds to store for each imported day, kept in order of first appearance
+    let mut days: Vec<NaiveDate> = Vec::new();
+    let mut totals: HashMap<NaiveDate, i32> = HashMap::new();
+
+    for (row, import_row) in rows {
+        if import_row.date > today {
+            plan.errors.push(row_error(
+                row,
+                format!("Date is in the future: {}", import_row.date),
+            ));
+            continue;
+        }
+        if import_row.minutes < 0 || import_row.minutes > MINUTES_PER_DAY {
+            plan.errors.push(row_error(
+                row,
+                format!("Minutes must be between 0 and {}", MINUTES_PER_DAY),
+            ));
+            continue;
+        }
+
+        let seconds = import_row.minutes * 60;
+        let on_record = existing.contains_key(&import_row.date);
+        let seen = totals.contains_key(&import_row.date);
+
+        if (on_record || seen) && policy == DuplicatePolicy::Reject {
+            plan.errors.push(row_error(
+                row,
+                format!("Duplicate date: {}", import_row.date),
+            ));
+            continue;
+        }
+
+        // Summed rows build on top of time already on record, and
+        // can't add up to more than a day
+        let total = match policy {
+            DuplicatePolicy::Sum => totals
+                .get(&import_row.date)
+                .or_else(|| existing.get(&import_row.date))
+                .copied()
+                .unwrap_or(0)
+                .checked_add(seconds)
+                .filter(|total| *total <= MINUTES_PER_DAY * 60),
+            _ => Some(seconds),
+        };
+        let total = match total {
+            Some(total) => total,
+            None => {
+                plan.errors.push(row_error(
+                    row,
+                    format!("Total time exceeds a day: {}", import_row.date),
+                ));
+                continue;
+            }
+        };
+
+        if !seen {
+            days.push(import_row.date);
+        }
+        totals.insert(import_row.date, total);
+    }
+
+    for day in days {
+        let seconds = totals[&day];
+        if existing.contains_key(&day) {
+            plan.updates.push((day, seconds));
+        } else {
+            plan.inserts.push((day, seconds));
+        }
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 3, d)
+    }
+
+    #[test]
+    fn parse_csv_skips_header_and_reports_bad_rows() {
+        let body = "date,minutes\n2021-03-01,30\n2021-13-01,10\n2021-03-02,ten\n2021-03-03\n";
+        let (rows, errors) = parse_csv(body);
+
+        assert_eq!(
+            rows,
+            vec![(
+                2,
+                ImportRow {
+                    date: day(1),
+                    minutes: 30
+                }
+            )]
+        );
+        assert_eq!(
+            errors.iter().map(|error| error.row).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn parse_json_reports_bad_rows() {
+        let body =
+            r#"[{"date": "2021-03-01", "minutes": 45}, {"date": "yesterday", "minutes": 5}]"#;
+        let (rows, errors) = parse_json(body).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 2);
+        assert!(parse_json("{}").is_err());
+    }
+
+    #[test]
+    fn plan_import_rejects_duplicates() {
+        let mut existing = HashMap::new();
+        existing.insert(day(1), 600);
+        let rows = vec![
+            (
+                1,
+                ImportRow {
+                    date: day(1),
+                    minutes: 20,
+                },
+            ),
+            (
+                2,
+                ImportRow {
+                    date: day(2),
+                    minutes: 20,
+                },
+            ),
+            (
+                3,
+                ImportRow {
+                    date: day(2),
+                    minutes: 5,
+                },
+            ),
+        ];
+
+        let plan = plan_import(rows, &existing, DuplicatePolicy::Reject, day(31));
+
+        assert_eq!(plan.inserts, vec![(day(2), 1200)]);
+        assert!(plan.updates.is_empty());
+        assert_eq!(
+            plan.errors
+                .iter()
+                .map(|error| error.row)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn plan_import_sums_and_replaces_duplicates() {
+        let mut existing = HashMap::new();
+        existing.insert(day(1), 600);
+        let rows = || {
+            vec![
+                (
+                    1,
+                    ImportRow {
+                        date: day(1),
+                        minutes: 20,
+                    },
+                ),
+                (
+                    2,
+                    ImportRow {
+                        date: day(2),
+                        minutes: 20,
+                    },
+                ),
+                (
+                    3,
+                    ImportRow {
+                        date: day(2),
+                        minutes: 5,
+                    },
+                ),
+            ]
+        };
+
+        let summed = plan_import(rows(), &existing, DuplicatePolicy::Sum, day(31));
+        assert_eq!(summed.updates, vec![(day(1), 1800)]);
+        assert_eq!(summed.inserts, vec![(day(2), 1500)]);
+
+        let replaced = plan_import(rows(), &existing, DuplicatePolicy::Replace, day(31));
+        assert_eq!(replaced.updates, vec![(day(1), 1200)]);
+        assert_eq!(replaced.inserts, vec![(day(2), 300)]);
+    }
+
+    #[test]
+    fn plan_import_rejects_sums_over_a_day() {
+        let mut existing = HashMap::new();
+        existing.insert(day(1), 1420 * 60);
+        let rows = vec![
+            (
+                1,
+                ImportRow {
+                    date: day(1),
+                    minutes: 30,
+                },
+            ),
+            (
+                2,
+                ImportRow {
+                    date: day(2),
+                    minutes: 1000,
+                },
+            ),
+            (
+                3,
+                ImportRow {
+                    date: day(2),
+                    minutes: 1000,
+                },
+            ),
+            (
+                4,
+                ImportRow {
+                    date: day(2),
+                    minutes: 440,
+                },
+            ),
+        ];
+
+        let plan = plan_import(rows, &existing, DuplicatePolicy::Sum, day(31));
+
+        assert!(plan.updates.is_empty());
+        assert_eq!(plan.inserts, vec![(day(2), 1440 * 60)]);
+        assert_eq!(
+            plan.errors
+                .iter()
+                .map(|error| error.row)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn plan_import_validates_dates_and_minutes() {
+        let rows = vec![
+            (
+                1,
+                ImportRow {
+                    date: day(20),
+                    minutes: 20,
+                },
+            ),
+            (
+                2,
+                ImportRow {
+                    date: day(2),
+                    minutes: 1441,
+                },
+            ),
+            (
+                3,
+                ImportRow {
+                    date: day(3),
+                    minutes: -1,
+                },
+            ),
+        ];
+
+        let plan = plan_import(rows, &HashMap::new(), DuplicatePolicy::Reject, day(10));
+
+        assert!(plan.inserts.is_empty());
+        assert_eq!(plan.errors.len(), 3);
+    }
+}
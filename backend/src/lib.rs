@@ -8,12 +8,18 @@ extern crate serde;
 extern crate rocket;
 
 use crate::auth::auth0::{AuthParameters, SessionDB, Settings};
+use crate::auth::jwks::JwksCache;
+use crate::auth::sessions::{configured_storage, spawn_session_cleanup};
+use crate::configuration::get_test_configuration;
+use crate::credentials::{install_keyring, keyring};
 use crate::db::operations::BlockplotDbConn;
+use crate::sync::{spawn_sync_worker, SyncHandle};
 
-use dashmap::DashMap;
+use chrono::Duration;
 
 use rocket::config::{Config, Environment, Value};
 use rocket::fairing::AdHoc;
+use rocket_contrib::databases::database_config;
 use rocket_contrib::templates::Template;
 use rocket_cors::{AllowedHeaders, AllowedOrigins};
 
@@ -22,8 +28,25 @@ use std::net::TcpListener;
 
 pub mod auth;
 pub mod configuration;
+pub mod credentials;
 pub mod db;
+pub mod errors;
+pub mod export;
+pub mod goals;
+pub mod import;
+pub mod palettes;
+pub mod render;
 pub mod routes;
+pub mod sources;
+pub mod stats;
+pub mod sync;
+
+// Minutes between background syncs of a user's skillblocks, unless
+// overridden by sync_interval_minutes in Rocket.toml
+const DEFAULT_SYNC_INTERVAL_MINUTES: i64 = 60;
+
+// Minutes between removals of expired sessions
+const SESSION_CLEANUP_MINUTES: i64 = 60;
 
 pub fn rocket(
     testing: bool,
@@ -41,8 +64,6 @@ pub fn rocket(
     }
     .to_cors();
 
-    let sessions = SessionDB(DashMap::new());
-
     let rocket: rocket::Rocket;
 
     // Setup rocket instance based on whether running integrations tests or not
@@ -54,45 +75,150 @@ pub fn rocket(
             .port(port)
             .extra("databases", db_config.unwrap())
             .finalize();
-        rocket = rocket::custom(config.unwrap()).attach(AdHoc::on_attach(
-            "Parameters Config",
-            |rocket| {
+        rocket = rocket::custom(config.unwrap())
+            .attach(AdHoc::on_attach("Parameters Config", |rocket| {
                 let settings = Settings::new().unwrap();
                 let auth_parameters = AuthParameters::new_testing(settings).unwrap();
+                let jwks = JwksCache::new(&auth_parameters.auth0_domain);
 
-                Ok(rocket.manage(auth_parameters))
-            },
-        ));
+                Ok(rocket.manage(auth_parameters).manage(jwks))
+            }))
+            .attach(AdHoc::on_attach("Test Credential Keys", |rocket| {
+                // Integration tests seal credentials with keys of
+                // test configuration
+                let keys = get_test_configuration()
+                    .ok()
+                    .and_then(|settings| settings.credentials.keys);
+                match keys.map(|keys| install_keyring(&keys)) {
+                    Some(Ok(_)) => Ok(rocket),
+                    _ => {
+                        println!("Error reading credential keys of test configuration");
+                        Err(rocket)
+                    }
+                }
+            }))
+            // Integration tests don't sync with real time sources
+            .manage(SyncHandle::disabled());
     } else {
         rocket = rocket::ignite()
             .attach(Template::fairing())
             .attach(AdHoc::on_attach("Parameters Config", |rocket| {
                 let config = rocket.config();
                 let auth_parameters = AuthParameters::new(config).unwrap();
+                let jwks = JwksCache::new(&auth_parameters.auth0_domain);
+
+                Ok(rocket.manage(auth_parameters).manage(jwks))
+            }))
+            .attach(AdHoc::on_attach("Sync Worker", |rocket| {
+                // Worker opens its own connections outside of the request pool
+                let database_url = match database_config("postgres_blockplot", rocket.config()) {
+                    Ok(config) => config.url.to_string(),
+                    Err(error) => {
+                        println!("Error reading database config for sync worker: {}", error);
+                        return Err(rocket);
+                    }
+                };
+                let interval = rocket
+                    .config()
+                    .get_int("sync_interval_minutes")
+                    .unwrap_or(DEFAULT_SYNC_INTERVAL_MINUTES);
+                let sync_handle = spawn_sync_worker(database_url, Duration::minutes(interval));
 
-                Ok(rocket.manage(auth_parameters))
+                Ok(rocket.manage(sync_handle))
             }));
     }
 
     rocket
         .attach(cors.unwrap())
         .attach(BlockplotDbConn::fairing())
+        .attach(AdHoc::on_attach("Credential Keys", |rocket| {
+            // Fail at launch rather than on first login when keys
+            // are missing or malformed
+            match keyring() {
+                Ok(_) => Ok(rocket),
+                Err(error) => {
+                    println!("Error reading credential keys: {}", error);
+                    Err(rocket)
+                }
+            }
+        }))
+        .attach(AdHoc::on_attach("Session Store", |rocket| {
+            let storage = match configured_storage(rocket.config()) {
+                Ok(storage) => storage,
+                Err(error) => {
+                    println!("Error setting up session store: {}", error);
+                    return Err(rocket);
+                }
+            };
+            // Cleanup opens its own connections outside of the request pool
+            let database_url = match database_config("postgres_blockplot", rocket.config()) {
+                Ok(config) => config.url.to_string(),
+                Err(error) => {
+                    println!(
+                        "Error reading database config for session cleanup: {}",
+                        error
+                    );
+                    return Err(rocket);
+                }
+            };
+            spawn_session_cleanup(
+                storage.clone(),
+                database_url,
+                Duration::minutes(SESSION_CLEANUP_MINUTES),
+            );
+
+            Ok(rocket.manage(SessionDB(storage)))
+        }))
         .mount(
             "/",
             routes![
+                routes::account::delete_account,
+                routes::account::request_account_deletion,
+                routes::api_tokens::create_api_token,
+                routes::api_tokens::get_api_tokens,
+                routes::api_tokens::get_api_token,
+                routes::api_tokens::revoke_api_token,
                 routes::authentication::auth0_login,
+                routes::authentication::logout_all_devices,
                 routes::authentication::process_login,
                 routes::authentication::process_logout,
+                routes::entries::create_entry,
+                routes::entries::delete_entry,
+                routes::entries::update_entry,
+                routes::export::export_data,
+                routes::goals::get_goal,
+                routes::goals::remove_goal,
+                routes::goals::set_goal,
                 routes::health::health_check,
+                routes::import::import_time_data,
                 routes::index::home,
                 routes::index::index,
+                routes::palettes::get_palettes,
+                routes::share::get_grid_png,
+                routes::share::get_grid_svg,
+                routes::share::get_shared_grid_png,
+                routes::share::get_shared_grid_svg,
+                routes::share::share_skillblock,
+                routes::share::unshare_skillblock,
+                routes::skillblocks::create_skillblock_json,
+                routes::skillblocks::edit_skillblock,
+                routes::skillblocks::get_skillblock,
+                routes::skillblocks::get_skillblock_stats,
                 routes::skillblocks::get_skillblocks,
                 routes::skillblocks::get_skillblocks_redirect,
                 routes::skillblocks::new_skillblock,
                 routes::skillblocks::new_skillblock_redirect,
+                routes::skillblocks::remove_skillblock,
             ],
         )
-        .manage(sessions)
+        .register(catchers![
+            errors::bad_request,
+            errors::unauthorized,
+            errors::forbidden,
+            errors::not_found,
+            errors::unprocessable_entity,
+            errors::internal_error,
+        ])
 }
 
 #[cfg(test)]
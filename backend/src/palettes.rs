@@ -0,0 +1,221 @@
+use crate::db::models::{Appearance, Skillblock};
+use crate::import::MINUTES_PER_DAY;
+
+use anyhow::{anyhow, Error};
+
+use std::str::FromStr;
+
+// Number of shades days having time data are split into
+pub const LEVELS: usize = 5;
+
+// Palette and thresholds new skillblocks start out with. Thresholds
+// are upper bounds in minutes of every shade but the last
+pub const DEFAULT_PALETTE: &str = "classic";
+pub const DEFAULT_THRESHOLDS: [i32; LEVELS - 1] = [15, 30, 45, 60];
+
+// Set of colours a block grid is shaded with. First colour is used
+// for days without time data, the rest for each shade in rising order
+#[derive(Debug, Serialize)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colorblind_safe: bool,
+    pub colors: [&'static str; LEVELS + 1],
+}
+
+pub const PALETTES: [Palette; 5] = [
+    Palette {
+        name: "classic",
+        colorblind_safe: false,
+        colors: [
+            "#dadada", "#dac695", "#f28a00", "#fd4600", "#f1230b", "#bc1c2a",
+        ],
+    },
+    Palette {
+        name: "forest",
+        colorblind_safe: false,
+        colors: [
+            "#dadada", "#c6e48b", "#9be9a8", "#40c463", "#30a14e", "#216e39",
+        ],
+    },
+    Palette {
+        name: "ocean",
+        colorblind_safe: false,
+        colors: [
+            "#dadada", "#c6dbef", "#9ecae1", "#6baed6", "#3182bd", "#08519c",
+        ],
+    },
+    Palette {
+        name: "viridis",
+        colorblind_safe: true,
+        colors: [
+            "#dadada", "#fde725", "#5ec962", "#21918c", "#3b528b", "#440154",
+        ],
+    },
+    Palette {
+        name: "cividis",
+        colorblind_safe: true,
+        colors: [
+            "#dadada", "#fee838", "#c3b369", "#7f7c75", "#3e4f6b", "#00204c",
+        ],
+    },
+];
+
+// Look up built in palette by name
+pub fn palette(name: &str) -> Option<&'static Palette> {
+    PALETTES.iter().find(|palette| palette.name == name)
+}
+
+// How time of a day is mapped to a shade. Fixed scales use thresholds
+// stored on the skillblock, quantile scales split days having time data
+// into equally sized groups
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntensityScale {
+    Fixed,
+    Quantile,
+}
+
+impl IntensityScale {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntensityScale::Fixed => "fixed",
+            IntensityScale::Quantile => "quantile",
+        }
+    }
+}
+
+impl FromStr for IntensityScale {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fixed" => Ok(IntensityScale::Fixed),
+            "quantile" => Ok(IntensityScale::Quantile),
+            _ => Err(anyhow!("Unknown intensity scale: {}", value)),
+        }
+    }
+}
+
+// Check thresholds hold one rising bound per shade, all within a single day
+pub fn validate_thresholds(thresholds: &[i32]) -> Result<(), String> {
+    if thresholds.len() != LEVELS - 1 {
+        return Err(format!("Exactly {} thresholds are required", LEVELS - 1));
+    }
+    if thresholds[0] < 1 || thresholds[LEVELS - 2] > MINUTES_PER_DAY {
+        return Err(format!(
+            "Thresholds must be between 1 and {}",
+            MINUTES_PER_DAY
+        ));
+    }
+    if thresholds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(String::from("Thresholds must be in rising order"));
+    }
+
+    Ok(())
+}
+
+// Thresholds a grid is shaded with. Quantile scales derive their bounds
+// from minutes of days having time data, falling back to stored
+// thresholds while there is no time data
+pub fn resolve_thresholds(scale: IntensityScale, thresholds: &[i32], minutes: &[i32]) -> Vec<i32> {
+    let mut active: Vec<i32> = minutes.iter().cloned().filter(|time| *time > 0).collect();
+    if scale == IntensityScale::Fixed || active.is_empty() {
+        return thresholds.to_vec();
+    }
+
+    // Nearest rank percentile closing each of the lower shades
+    active.sort();
+    (1..LEVELS)
+        .map(|level| {
+            let rank = (level as f64 * active.len() as f64 / LEVELS as f64).ceil() as usize;
+            active[rank.max(1) - 1]
+        })
+        .collect()
+}
+
+// Shade of a day, where 0 means no time data and LEVELS the highest shade
+pub fn level(minutes: i32, thresholds: &[i32]) -> usize {
+    if minutes <= 0 {
+        return 0;
+    }
+
+    thresholds
+        .iter()
+        .position(|threshold| minutes <= *threshold)
+        .map_or(LEVELS, |index| index + 1)
+}
+
+// Resolve palette and thresholds a skillblock is shaded with, given minutes
+// of each day shown. Unknown palettes fall back to the default palette
+pub fn appearance(skillblock: &Skillblock, minutes: &[i32]) -> Appearance {
+    let palette = palette(&skillblock.palette).unwrap_or(&PALETTES[0]);
+    let scale = skillblock
+        .intensity_scale
+        .parse()
+        .unwrap_or(IntensityScale::Fixed);
+
+    Appearance {
+        palette: palette.name.to_string(),
+        colors: palette
+            .colors
+            .iter()
+            .map(|color| color.to_string())
+            .collect(),
+        intensity_scale: scale.as_str().to_string(),
+        thresholds: resolve_thresholds(scale, &skillblock.thresholds, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_thresholds_match_original_buckets() {
+        let thresholds = DEFAULT_THRESHOLDS;
+
+        assert_eq!(level(0, &thresholds), 0);
+        assert_eq!(level(1, &thresholds), 1);
+        assert_eq!(level(15, &thresholds), 1);
+        assert_eq!(level(16, &thresholds), 2);
+        assert_eq!(level(60, &thresholds), 4);
+        assert_eq!(level(61, &thresholds), 5);
+        assert!(validate_thresholds(&thresholds).is_ok());
+    }
+
+    #[test]
+    fn quantile_scale_splits_active_days_evenly() {
+        let minutes: Vec<i32> = (0..=10).map(|day| day * 10).collect();
+        let thresholds =
+            resolve_thresholds(IntensityScale::Quantile, &DEFAULT_THRESHOLDS, &minutes);
+
+        // Day without time data is left out of the ten active days
+        assert_eq!(thresholds, vec![20, 40, 60, 80]);
+        let levels: Vec<usize> = minutes
+            .iter()
+            .map(|time| level(*time, &thresholds))
+            .collect();
+        assert_eq!(levels, vec![0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5]);
+    }
+
+    #[test]
+    fn quantile_scale_falls_back_without_time_data() {
+        let thresholds = resolve_thresholds(IntensityScale::Quantile, &[5, 10, 20, 30], &[0, 0]);
+
+        assert_eq!(thresholds, vec![5, 10, 20, 30]);
+    }
+
+    #[test]
+    fn invalid_thresholds_are_rejected() {
+        assert!(validate_thresholds(&[15, 30, 45]).is_err());
+        assert!(validate_thresholds(&[0, 30, 45, 60]).is_err());
+        assert!(validate_thresholds(&[15, 45, 30, 60]).is_err());
+        assert!(validate_thresholds(&[15, 30, 45, 2000]).is_err());
+    }
+
+    #[test]
+    fn palettes_have_a_colour_per_shade_and_colorblind_option() {
+        assert!(palette(DEFAULT_PALETTE).is_some());
+        assert!(PALETTES.iter().any(|palette| palette.colorblind_safe));
+        assert!(palette("neon").is_none());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::db::models::Appearance;
+use crate::palettes::level;
+use crate::stats::SkillblockStats;
+
+use anyhow::{anyhow, Error};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use once_cell::sync::Lazy;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Horizontal space taken by each week column, and vertical
+// space taken by each weekday row, matching the block grid
+const WEEK_WIDTH: usize = 14;
+const DAY_HEIGHT: u32 = 15;
+const GRID_HEIGHT: usize = 128;
+const STATS_HEIGHT: usize = 50;
+
+// Weekday labels shown left of grid, as (label, dy) pairs
+const WEEKDAY_LABELS: [(&str, u32); 3] = [("Mon", 25), ("Wed", 56), ("Fri", 85)];
+
+// Render standalone svg of a skillblock grid covering start to end.
+// Layout follows the block grid of the frontend: one column per week
+// starting on sunday, month labels above the first week of each month
+// and weekday labels on the left. Time data holds seconds per day
+pub fn render_grid_svg(
+    title: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    time_data: &HashMap<NaiveDate, i32>,
+    appearance: &Appearance,
+) -> String {
+    let (grid, width) = grid_body(start, end, time_data, appearance);
+
+    svg_document(title, width, GRID_HEIGHT, &grid)
+}
+
+// Render skillblock grid with its stats row underneath, matching
+// the stats labels of the frontend. Drawn on a white background,
+// since raster exports have nothing behind them
+pub fn render_card_svg(
+    title: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    time_data: &HashMap<NaiveDate, i32>,
+    appearance: &Appearance,
+    stats: &SkillblockStats,
+) -> String {
+    let (grid, width) = grid_body(start, end, time_data, appearance);
+
+    let labels = [
+        ("Average Daily Time", hours_minutes(stats.daily_mean)),
+        ("Max Time In A Day", hours_minutes(stats.daily_max)),
+        (
+            "Longest Day Chain",
+            format!("{} day(s)", stats.longest_streak.days),
+        ),
+        (
+            "Current Day Chain",
+            format!("{} day(s)", stats.current_streak.days),
+        ),
+    ];
+    let column_width = (width - 30) / labels.len();
+    let mut row = String::new();
+    for (index, (heading, value)) in labels.iter().enumerate() {
+        let _ = write!(
+            row,
+            r##"<g transform="translate({}, 0)"><text y="12" font-size="11" fill="#7a7a7a">{}</text><text y="32" font-size="18" font-weight="bold">{}</text></g>"##,
+            index * column_width,
+            heading,
+            value
+        );
+    }
+
+    let body = format!(
+        r##"<rect width="100%" height="100%" fill="#ffffff"/>{}<g transform="translate(30, {})">{}</g>"##,
+        grid, GRID_HEIGHT, row
+    );
+
+    svg_document(title, width, GRID_HEIGHT + STATS_HEIGHT, &body)
+}
+
+// Options used to rasterize svgs. System fonts are loaded once, on
+// first use, as scanning them takes far longer than rendering
+static RENDER_OPTIONS: Lazy<usvg::Options> = Lazy::new(|| {
+    let mut options = usvg::Options::default();
+    options.fontdb.load_system_fonts();
+    if options.fontdb.len() == 0 {
+        println!("No system fonts found, png images will be rendered without labels");
+    }
+
+    options
+});
+
+// Rasterize svg into png image. Labels are drawn with fonts installed
+// on the system, so no browser or display is needed
+pub fn render_png(svg: &str) -> Result<Vec<u8>, Error> {
+    let tree = usvg::Tree::from_str(svg, &RENDER_OPTIONS.to_ref())?;
+    let size = tree.svg_node().size.to_screen_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| anyhow!("Image has no area"))?;
+    resvg::render(&tree, usvg::FitTo::Original, pixmap.as_mut())
+        .ok_or_else(|| anyhow!("Image couldn't be rendered"))?;
+
+    Ok(pixmap.encode_png()?)
+}
+
+// Build grid markup covering start to end, along with width it takes up
+fn grid_body(
+    start: NaiveDate,
+    end: NaiveDate,
+    time_data: &HashMap<NaiveDate, i32>,
+    appearance: &Appearance,
+) -> (String, usize) {
+    let mut weeks = String::new();
+    let mut months = String::new();
+    let mut week = String::new();
+    let mut column = 0;
+
+    let mut day = start;
+    while day <= end {
+        if day.weekday() == Weekday::Sun && day != start {
+            close_week(&mut weeks, &mut week, column);
+            column += 1;
+        }
+        if day.weekday() == Weekday::Sun && day.day() <= 7 {
+            let _ = write!(
+                months,
+                r#"<text class="month" y="-7" x="{}" font-size="12">{}</text>"#,
+                column * WEEK_WIDTH,
+                day.format("%b")
+            );
+        }
+
+        let minutes = time_data.get(&day).cloned().unwrap_or(0) / 60;
+        let fill = appearance
+            .colors
+            .get(level(minutes, &appearance.thresholds))
+            .or_else(|| appearance.colors.last())
+            .map_or("none", |color| color.as_str());
+        let _ = write!(
+            week,
+            r##"<rect width="11" height="11" y="{}" rx="2" ry="2" fill="{}" data-date="{}" data-minutes="{}"><title>{}: {} minute(s)</title></rect>"##,
+            day.weekday().num_days_from_sunday() * DAY_HEIGHT,
+            escape(fill),
+            day,
+            minutes,
+            day,
+            minutes
+        );
+
+        day += Duration::days(1);
+    }
+    close_week(&mut weeks, &mut week, column);
+
+    let mut weekdays = String::new();
+    for (label, dy) in WEEKDAY_LABELS.iter() {
+        let _ = write!(
+            weekdays,
+            r#"<text text-anchor="start" class="wday" dx="-30" dy="{}" font-size="12">{}</text>"#,
+            dy, label
+        );
+    }
+
+    let grid = format!(
+        r#"<g transform="translate(30, 20)">{}{}{}</g>"#,
+        weeks, months, weekdays
+    );
+
+    (grid, 30 + (column + 1) * WEEK_WIDTH + 10)
+}
+
+fn svg_document(title: &str, width: usize, height: usize, body: &str) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif"><title>{}</title>{}</svg>"#,
+        width,
+        height,
+        escape(title),
+        body
+    )
+}
+
+// Format seconds as hours and minutes, such as 1h 5m
+fn hours_minutes(seconds: i32) -> String {
+    let minutes = seconds / 60;
+
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+// Wrap days gathered for a week into its column
+fn close_week(weeks: &mut String, week: &mut String, column: usize) {
+    if week.is_empty() {
+        return;
+    }
+
+    let _ = write!(
+        weeks,
+        r#"<g transform="translate({}, 0)">{}</g>"#,
+        column * WEEK_WIDTH,
+        week
+    );
+    week.clear();
+}
+
+// Escape text placed inside svg markup
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stats::skillblock_stats;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn appearance() -> Appearance {
+        Appearance {
+            palette: String::from("classic"),
+            colors: [
+                "#000000", "#111111", "#222222", "#333333", "#444444", "#555555",
+            ]
+            .iter()
+            .map(|color| color.to_string())
+            .collect(),
+            intensity_scale: String::from("fixed"),
+            thresholds: vec![15, 30, 45, 60],
+        }
+    }
+
+    #[test]
+    fn grid_has_a_rect_per_day_and_a_column_per_week() {
+        // January 1st 2021 was a friday, so 2021 spans 53 columns
+        let svg = render_grid_svg(
+            "Programming",
+            date(2021, 1, 1),
+            date(2021, 12, 31),
+            &HashMap::new(),
+            &appearance(),
+        );
+
+        assert_eq!(svg.matches("<rect").count(), 365);
+        // Week columns plus group offsetting grid from labels
+        assert_eq!(svg.matches(r#"<g transform="translate("#).count(), 53 + 1);
+        assert!(svg.contains(r#"<g transform="translate(728, 0)">"#));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    }
+
+    #[test]
+    fn days_are_shaded_by_level_and_placed_by_weekday() {
+        let mut time_data = HashMap::new();
+        time_data.insert(date(2021, 2, 9), 20 * 60);
+        time_data.insert(date(2021, 2, 10), 90 * 60);
+
+        let svg = render_grid_svg(
+            "Programming",
+            date(2021, 2, 7),
+            date(2021, 2, 13),
+            &time_data,
+            &appearance(),
+        );
+
+        assert!(svg.contains(r##"y="30" rx="2" ry="2" fill="#222222" data-date="2021-02-09""##));
+        assert!(svg.contains(r##"y="45" rx="2" ry="2" fill="#555555" data-date="2021-02-10""##));
+        assert!(svg.contains(r##"y="0" rx="2" ry="2" fill="#000000" data-date="2021-02-07""##));
+    }
+
+    #[test]
+    fn month_labels_sit_above_first_sunday_of_month() {
+        let svg = render_grid_svg(
+            "Programming",
+            date(2021, 1, 31),
+            date(2021, 3, 13),
+            &HashMap::new(),
+            &appearance(),
+        );
+
+        // February 7th and March 7th 2021 are in columns 1 and 5
+        assert!(svg.contains(r#"x="14" font-size="12">Feb</text>"#));
+        assert!(svg.contains(r#"x="70" font-size="12">Mar</text>"#));
+        assert!(!svg.contains(">Jan</text>"));
+    }
+
+    #[test]
+    fn card_adds_stats_row_below_grid() {
+        let mut time_data = HashMap::new();
+        time_data.insert(date(2021, 2, 9), 65 * 60);
+        let stats = skillblock_stats(&[(date(2021, 2, 9), 65 * 60)], date(2021, 2, 9));
+
+        let svg = render_card_svg(
+            "Programming",
+            date(2021, 2, 7),
+            date(2021, 2, 13),
+            &time_data,
+            &appearance(),
+            &stats,
+        );
+
+        assert!(svg.contains(r#"height="178""#));
+        assert!(svg.contains(r#"<g transform="translate(30, 128)">"#));
+        assert!(svg.contains(">1h 5m</text>"));
+        assert!(svg.contains(">1 day(s)</text>"));
+    }
+
+    #[test]
+    fn title_is_escaped() {
+        let svg = render_grid_svg(
+            "<script>&",
+            date(2021, 1, 1),
+            date(2021, 1, 1),
+            &HashMap::new(),
+            &appearance(),
+        );
+
+        assert!(svg.contains("<title>&lt;script&gt;&amp;</title>"));
+        assert!(!svg.contains("<script>"));
+    }
+}
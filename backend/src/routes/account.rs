@@ -0,0 +1,116 @@
+use crate::auth::auth0::{AuthParameters, SessionDB};
+use crate::auth::sessions::token_hash;
+use crate::db::models;
+use crate::db::operations::{
+    delete_expired_deletion_tokens, delete_user, take_deletion_token, upsert_deletion_token,
+    BlockplotDbConn,
+};
+use crate::errors::ApiError;
+
+use chrono::{Duration, Utc};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use rocket::http::{Cookie, Cookies, Status};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+use std::iter;
+
+use subtle::ConstantTimeEq;
+
+// Length of account deletion confirmation tokens
+const CONFIRMATION_TOKEN_LENGTH: usize = 32;
+
+// Minutes a confirmation token stays valid after being issued
+const CONFIRMATION_TOKEN_MINUTES: i64 = 10;
+
+// Issue a short lived token that has to be sent back to delete the
+// account, so a single stray request can't delete it. Requesting
+// again replaces any token issued before. Only its hash is stored,
+// so issued tokens survive restarts and are shared between instances
+#[post("/api/account/deletion")]
+pub fn request_account_deletion(
+    conn: BlockplotDbConn,
+    user: models::SessionUser,
+) -> Result<Json<models::DeletionConfirmation>, ApiError> {
+    let user = user.0;
+    let mut rng = thread_rng();
+    let token: String = iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(CONFIRMATION_TOKEN_LENGTH)
+        .collect();
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(CONFIRMATION_TOKEN_MINUTES);
+
+    // Drop tokens that expired without being used, so
+    // abandoned deletion requests don't pile up
+    delete_expired_deletion_tokens(&conn, now.timestamp())?;
+    upsert_deletion_token(
+        &conn,
+        &models::NewDeletionToken {
+            user_id: user.user_id,
+            token_hash: token_hash(&token),
+            expires: expires_at.timestamp(),
+        },
+    )?;
+
+    Ok(Json(models::DeletionConfirmation {
+        confirmation_token: token,
+        expires_at: expires_at.naive_utc(),
+    }))
+}
+
+// Delete account of user along with all of their data. Skillblocks,
+// time data, sync ranges and goals are removed by cascade. Every
+// session of user is dropped, logging them out on all devices
+#[delete("/api/account", format = "json", data = "<deletion>")]
+pub fn delete_account(
+    conn: BlockplotDbConn,
+    mut cookies: Cookies,
+    deletion: Json<models::AccountDeletion>,
+    session_db: State<SessionDB>,
+    settings: State<AuthParameters>,
+    user: models::SessionUser,
+) -> Result<Status, ApiError> {
+    let user = user.0;
+
+    // Tokens are single use, so remove it whether or not it matches
+    let issued = take_deletion_token(&conn, user.user_id)?;
+    let confirmed = match issued {
+        Some(issued) => {
+            // Compared in constant time, so token can't be
+            // worked out from how long a mismatch takes
+            let matches: bool = issued
+                .token_hash
+                .as_bytes()
+                .ct_eq(token_hash(&deletion.confirmation_token).as_bytes())
+                .into();
+            matches && issued.expires > Utc::now().timestamp()
+        }
+        None => false,
+    };
+    if !confirmed {
+        return Err(ApiError::forbidden(
+            "invalid_confirmation_token",
+            "Confirmation token is missing, expired or doesn't match",
+        ));
+    }
+
+    delete_user(&conn, user.user_id)?;
+
+    if let Err(error) = session_db.0.store(&conn).remove_user(&user.auth_id) {
+        println!("Error removing sessions of deleted user: {}", error);
+    }
+    cookies.remove(Cookie::named("session"));
+
+    // Local data is already gone, so a failure here is
+    // logged rather than reported back to user
+    if settings.delete_identity {
+        if let Err(error) = settings.delete_auth0_identity(&user.auth_id) {
+            println!("Error deleting auth0 identity: {}", error);
+        }
+    }
+
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,106 @@
+use crate::auth::api_tokens::{generate_token, TokenScope};
+use crate::auth::sessions::token_hash;
+use crate::db::models;
+use crate::db::models::{ApiTokenData, NewApiToken};
+use crate::db::operations::{
+    count_active_api_tokens, insert_api_token, query_api_token, query_api_tokens,
+    update_api_token_revoked, BlockplotDbConn,
+};
+use crate::errors::ApiError;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+
+// Most unrevoked api tokens a user can hold
+const MAX_ACTIVE_TOKENS: i64 = 20;
+
+// Longest name an api token can be given
+const MAX_NAME_LENGTH: usize = 64;
+
+// Create api token for user. Token is sent back this
+// once, only its hash is kept in the database
+#[post("/api/tokens", format = "json", data = "<token_data>")]
+pub fn create_api_token(
+    conn: BlockplotDbConn,
+    token_data: Json<ApiTokenData>,
+    user: models::SessionUser,
+) -> Result<status::Created<Json<models::IssuedApiToken>>, ApiError> {
+    let user = user.0;
+    let name = token_data.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(ApiError::unprocessable(
+            "invalid_token_name",
+            &format!(
+                "Token name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            ),
+        ));
+    }
+    let scope: TokenScope = token_data.scope.parse().map_err(|_| {
+        ApiError::unprocessable("invalid_token_scope", "Scope must be read or read_write")
+    })?;
+    if count_active_api_tokens(&conn, &user)? >= MAX_ACTIVE_TOKENS {
+        return Err(ApiError::forbidden(
+            "api_token_limit_reached",
+            "Api token limit reached, revoke a token to create another",
+        ));
+    }
+
+    let token = generate_token();
+    let new_token = NewApiToken {
+        user_id: user.user_id,
+        name: name.to_string(),
+        token_hash: token_hash(&token),
+        scope: scope.as_str().to_string(),
+    };
+    let api_token = insert_api_token(&conn, &new_token)?;
+    let location = format!("/api/tokens/{}", api_token.id);
+
+    Ok(status::Created(
+        location,
+        Some(Json(models::IssuedApiToken { token, api_token })),
+    ))
+}
+
+// Serve api tokens of user, revoked ones included, without the tokens themselves
+#[get("/api/tokens")]
+pub fn get_api_tokens(
+    conn: BlockplotDbConn,
+    user: models::SessionUser,
+) -> Result<Json<Vec<models::ApiToken>>, ApiError> {
+    Ok(Json(query_api_tokens(&conn, &user.0)?))
+}
+
+// Serve single api token of user, without the token itself
+#[get("/api/tokens/<token_id>")]
+pub fn get_api_token(
+    token_id: i32,
+    conn: BlockplotDbConn,
+    user: models::SessionUser,
+) -> Result<Json<models::ApiToken>, ApiError> {
+    match query_api_token(&conn, &user.0, token_id)? {
+        Some(api_token) => Ok(Json(api_token)),
+        None => Err(ApiError::not_found(
+            "api_token_not_found",
+            "Api token not found",
+        )),
+    }
+}
+
+// Revoke api token of user. Revoked tokens stay listed,
+// but no longer authenticate requests
+#[delete("/api/tokens/<token_id>")]
+pub fn revoke_api_token(
+    token_id: i32,
+    conn: BlockplotDbConn,
+    user: models::SessionUser,
+) -> Result<Status, ApiError> {
+    match update_api_token_revoked(&conn, &user.0, token_id)? {
+        0 => Err(ApiError::not_found(
+            "api_token_not_found",
+            "Api token not found or already revoked",
+        )),
+        _ => Ok(Status::NoContent),
+    }
+}
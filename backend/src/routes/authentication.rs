@@ -2,7 +2,10 @@ use crate::auth::auth0::{
     build_random_state, decode_and_validate, get_or_create_user, AuthParameters, Session,
     SessionDB, TokenResponse,
 };
+use crate::auth::jwks::JwksCache;
+use crate::db::models;
 use crate::db::operations::{update_user_login_timestamp, BlockplotDbConn};
+use crate::errors::ApiError;
 
 use rocket::http::{Cookie, Cookies, Status};
 use rocket::response::Redirect;
@@ -32,6 +35,7 @@ pub fn process_login(
     code: String,
     mut cookies: Cookies,
     conn: BlockplotDbConn,
+    jwks: State<JwksCache>,
     state: String,
     settings: State<AuthParameters>,
 ) -> Result<Redirect, Status> {
@@ -62,10 +66,12 @@ pub fn process_login(
         settings.client_id.as_str(),
         settings.auth0_domain.as_str(),
         token_response.id_token.as_str(),
-        settings.client_secret.as_str(),
+        &jwks,
     )
-    .map_err(|_| Status::Unauthorized)
-    .unwrap();
+    .map_err(|error| {
+        println!("Error validating id token: {}", error);
+        Status::Unauthorized
+    })?;
 
     let user =
         get_or_create_user(&conn, &token_payload).map_err(|_| Status::InternalServerError)?;
@@ -87,7 +93,12 @@ pub fn process_login(
 
     session_db
         .0
-        .insert(session_token.to_string(), Some(new_session));
+        .store(&conn)
+        .insert(&session_token, new_session)
+        .map_err(|error| {
+            println!("Error storing session: {}", error);
+            Status::InternalServerError
+        })?;
 
     let cookie = Cookie::build("session", session_token)
         .path("/")
@@ -113,6 +124,7 @@ pub fn process_login(
 #[get("/logout")]
 pub fn process_logout(
     mut cookies: Cookies,
+    conn: BlockplotDbConn,
     session_db: State<SessionDB>,
     settings: State<AuthParameters>,
 ) -> Redirect {
@@ -120,7 +132,9 @@ pub fn process_logout(
         .get("session")
         .and_then(|cookie| cookie.value().parse().ok());
     if let Some(id) = session_id {
-        session_db.0.remove(&id);
+        if let Err(error) = session_db.0.store(&conn).remove(&id) {
+            println!("Error removing session: {}", error);
+        }
     }
     cookies.remove(Cookie::named("session"));
 
@@ -132,3 +146,25 @@ pub fn process_logout(
 
     Redirect::to(logout_request)
 }
+
+// Route logs user out on every device by removing all of their
+// sessions, including the one making the request
+#[delete("/api/sessions")]
+pub fn logout_all_devices(
+    mut cookies: Cookies,
+    conn: BlockplotDbConn,
+    session_db: State<SessionDB>,
+    user: models::SessionUser,
+) -> Result<Status, ApiError> {
+    session_db
+        .0
+        .store(&conn)
+        .remove_user(&user.0.auth_id)
+        .map_err(|error| {
+            println!("Error removing sessions: {}", error);
+            ApiError::internal()
+        })?;
+    cookies.remove(Cookie::named("session"));
+
+    Ok(Status::NoContent)
+}
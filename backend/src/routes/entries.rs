@@ -0,0 +1,138 @@
+use crate::db::models;
+use crate::db::models::{DayDate, EntryData, EntryTime, NewDateTime};
+use crate::db::operations::{
+    create_date_time, delete_date_time_by_day, query_date_time, query_owned_skillblock,
+    update_date_time, BlockplotDbConn,
+};
+use crate::errors::ApiError;
+use crate::import::MINUTES_PER_DAY;
+use crate::sources::TimeSourceKind;
+
+use diesel::result::{DatabaseErrorKind, Error};
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+
+// Fetch skillblock owned by user and make sure time can be entered
+// for it by hand. That's manual skillblocks only. Every skillblock
+// with a time source, RescueTime offline categories included, has its
+// recent time data refetched and overwritten on every sync
+fn entry_skillblock(
+    conn: &BlockplotDbConn,
+    user: &models::User,
+    block_id: i32,
+) -> Result<models::Skillblock, ApiError> {
+    let skillblock = query_owned_skillblock(conn, user, block_id).map_err(|error| match error {
+        Error::NotFound => ApiError::not_found("skillblock_not_found", "Skillblock not found"),
+        _ => ApiError::from(error),
+    })?;
+
+    if skillblock.source != TimeSourceKind::Manual.as_str() {
+        return Err(ApiError::unprocessable(
+            "entries_not_allowed",
+            "Time data of skillblock is synced from its time source",
+        ));
+    }
+
+    Ok(skillblock)
+}
+
+// Validate entered minutes fall within a single day
+fn validate_minutes(minutes: i32) -> Result<(), ApiError> {
+    if minutes < 1 || minutes > MINUTES_PER_DAY {
+        return Err(ApiError::unprocessable(
+            "invalid_minutes",
+            &format!("Minutes must be between 1 and {}", MINUTES_PER_DAY),
+        ));
+    }
+
+    Ok(())
+}
+
+// Create time entry for a given skillblock and day. Time data is
+// stored in seconds, matching data pulled from external time sources
+#[post(
+    "/api/skillblocks/<block_id>/entries",
+    format = "json",
+    data = "<entry>"
+)]
+pub fn create_entry(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    entry: Json<EntryData>,
+    user: models::User,
+) -> Result<status::Created<Json<models::DateTime>>, ApiError> {
+    let skillblock = entry_skillblock(&conn, &user, block_id)?;
+    validate_minutes(entry.minutes)?;
+
+    let new_date_time = NewDateTime {
+        block_id: Some(skillblock.block_id),
+        day_time: entry.minutes * 60,
+        day_date: entry.day_date.and_hms(0, 0, 0),
+    };
+
+    // Deliberately a plain insert rather than the upsert every other
+    // write path uses. Creating an entry for a day already on record
+    // answers 409, so a client can't overwrite time it didn't know
+    // about. Existing entries are changed through update_entry
+    let date_time = create_date_time(&conn, new_date_time).map_err(|error| match error {
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            ApiError::conflict("entry_exists", "Day already has a time entry")
+        }
+        _ => ApiError::from(error),
+    })?;
+
+    let location = format!(
+        "/api/skillblocks/{}/entries/{}",
+        skillblock.block_id, entry.day_date
+    );
+
+    Ok(status::Created(location, Some(Json(date_time))))
+}
+
+// Update time entry of a given skillblock and day
+#[put(
+    "/api/skillblocks/<block_id>/entries/<day>",
+    format = "json",
+    data = "<entry>"
+)]
+pub fn update_entry(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    day: DayDate,
+    entry: Json<EntryTime>,
+    user: models::User,
+) -> Result<Json<models::DateTime>, ApiError> {
+    let skillblock = entry_skillblock(&conn, &user, block_id)?;
+    validate_minutes(entry.minutes)?;
+
+    let day_date = day.0.and_hms(0, 0, 0);
+    if update_date_time(&conn, skillblock.block_id, day_date, entry.minutes * 60)? == 0 {
+        return Err(entry_not_found());
+    }
+
+    let date_time = query_date_time(&conn, skillblock.block_id, day_date)?;
+
+    Ok(Json(date_time))
+}
+
+// Delete time entry of a given skillblock and day
+#[delete("/api/skillblocks/<block_id>/entries/<day>")]
+pub fn delete_entry(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    day: DayDate,
+    user: models::User,
+) -> Result<Status, ApiError> {
+    let skillblock = entry_skillblock(&conn, &user, block_id)?;
+
+    match delete_date_time_by_day(&conn, skillblock.block_id, day.0.and_hms(0, 0, 0))? {
+        0 => Err(entry_not_found()),
+        _ => Ok(Status::NoContent),
+    }
+}
+
+fn entry_not_found() -> ApiError {
+    ApiError::not_found("entry_not_found", "Day has no time entry")
+}
@@ -0,0 +1,39 @@
+use crate::db::models;
+use crate::db::operations::{query_date_times, query_skillblocks, BlockplotDbConn};
+use crate::errors::ApiError;
+use crate::export::build_archive;
+
+use rocket::http::ContentType;
+use rocket::response::Response;
+
+use std::io::Cursor;
+
+// Send user a zip archive of their profile, skillblocks and time data,
+// each as json and csv. Archive is built in memory, then streamed back
+// in chunks
+#[get("/api/export")]
+pub fn export_data(
+    conn: BlockplotDbConn,
+    user: models::User,
+) -> Result<Response<'static>, ApiError> {
+    let skillblocks = query_skillblocks(&conn, &user)?;
+
+    let mut date_times = Vec::new();
+    for skillblock in &skillblocks {
+        date_times.extend(query_date_times(&conn, skillblock)?);
+    }
+
+    let archive = build_archive(&user, &skillblocks, &date_times).map_err(|error| {
+        println!("Error building data export: {}", error);
+        ApiError::internal()
+    })?;
+
+    Ok(Response::build()
+        .header(ContentType::ZIP)
+        .raw_header(
+            "Content-Disposition",
+            "attachment; filename=\"blockplot-export.zip\"",
+        )
+        .streamed_body(Cursor::new(archive))
+        .finalize())
+}
@@ -0,0 +1,120 @@
+use crate::db::models;
+use crate::db::models::{GoalData, NewGoal};
+use crate::db::operations::{
+    delete_goal, query_date_times_between, query_goal, upsert_goal, BlockplotDbConn,
+};
+use crate::errors::ApiError;
+use crate::goals::{evaluate_goal, GoalPeriod, GoalProgress};
+use crate::routes::skillblocks::{date_range, owned_skillblock};
+use crate::sync::backfill::year_before;
+
+use chrono::{Local, NaiveDate};
+
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+
+// Serve goal of a skillblock owned by user, with met or missed status of
+// every period in range. Takes the same optional query parameters as
+// get_skillblocks, defaulting to the year leading up to today
+#[get("/api/skillblocks/<block_id>/goal?<from>&<to>&<year>")]
+pub fn get_goal(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    from: models::QueryParam<models::DayDate>,
+    to: models::QueryParam<models::DayDate>,
+    year: models::QueryParam<i32>,
+    user: models::User,
+) -> Result<Json<GoalProgress>, ApiError> {
+    let (from, to) = date_range(from, to, year)?;
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+    let goal = query_goal(&conn, &skillblock)?.ok_or_else(goal_not_found)?;
+    let period: GoalPeriod = goal.period.parse().map_err(|_| ApiError::internal())?;
+
+    let today = Local::now().date().naive_utc();
+    let end = to.map(|day| day.date()).unwrap_or(today);
+    let start = from
+        .map(|day| day.date())
+        .unwrap_or_else(|| year_before(end));
+
+    // Widen query to whole periods, so weeks cut by range are summed in full
+    let date_times: Vec<(NaiveDate, i32)> = query_date_times_between(
+        &conn,
+        &skillblock,
+        Some(period.start_of(start).and_hms(0, 0, 0)),
+        Some(period.end_of(period.start_of(end)).and_hms(0, 0, 0)),
+    )?
+    .into_iter()
+    .map(|(day_date, day_time)| (day_date.date(), day_time))
+    .collect();
+
+    let periods = evaluate_goal(&date_times, period, goal.target_minutes, start, end, today);
+    let count = |status: &str| {
+        periods
+            .iter()
+            .filter(|progress| progress.status == status)
+            .count() as i32
+    };
+
+    Ok(Json(GoalProgress {
+        met: count("met"),
+        missed: count("missed"),
+        goal,
+        periods,
+    }))
+}
+
+// Set goal of a skillblock owned by user, replacing any previous goal
+#[put(
+    "/api/skillblocks/<block_id>/goal",
+    format = "json",
+    data = "<goal_data>"
+)]
+pub fn set_goal(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    goal_data: Json<GoalData>,
+    user: models::User,
+) -> Result<Json<models::Goal>, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    let period: GoalPeriod = goal_data.period.parse().map_err(|_| {
+        ApiError::unprocessable("invalid_goal_period", "Period must be daily or weekly")
+    })?;
+    if goal_data.target_minutes < 1 || goal_data.target_minutes > period.max_minutes() {
+        return Err(ApiError::unprocessable(
+            "invalid_target_minutes",
+            &format!(
+                "Target minutes of a {} goal must be between 1 and {}",
+                period.as_str(),
+                period.max_minutes()
+            ),
+        ));
+    }
+
+    let new_goal = NewGoal {
+        block_id: skillblock.block_id,
+        period: period.as_str().to_string(),
+        target_minutes: goal_data.target_minutes,
+    };
+
+    Ok(Json(upsert_goal(&conn, &new_goal)?))
+}
+
+// Remove goal of a skillblock owned by user
+#[delete("/api/skillblocks/<block_id>/goal")]
+pub fn remove_goal(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    user: models::User,
+) -> Result<Status, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    match delete_goal(&conn, &skillblock)? {
+        0 => Err(goal_not_found()),
+        _ => Ok(Status::NoContent),
+    }
+}
+
+fn goal_not_found() -> ApiError {
+    ApiError::not_found("goal_not_found", "Skillblock has no goal")
+}
@@ -0,0 +1,123 @@
+use crate::db::models;
+use crate::db::models::NewDateTime;
+use crate::db::operations::{
+    query_date_times_desc, query_owned_skillblock, upsert_date_times, BlockplotDbConn,
+};
+use crate::errors::ApiError;
+use crate::import::{parse_csv, parse_json, plan_import, DuplicatePolicy, ImportReport};
+use crate::sources::TimeSourceKind;
+
+use chrono::Local;
+
+use diesel::result::Error;
+
+use rocket::data::Data;
+use rocket::http::{ContentType, Status};
+use rocket::response::status;
+use rocket_contrib::json::Json;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+// Largest import body accepted, roughly a decade of daily rows
+const IMPORT_LIMIT: u64 = 1024 * 1024;
+
+// Import historical time data for a skillblock. Accepts csv with
+// "date,minutes" rows, or a json array when sent as application/json.
+// Days already on record are rejected, replaced or summed depending on
+// the on_duplicate query parameter. Valid rows are stored and every
+// rejected row is reported back with its row number. Only manual
+// skillblocks take imports, time data of others is overwritten by sync
+#[post("/api/skillblocks/<block_id>/import?<on_duplicate>", data = "<data>")]
+pub fn import_time_data(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    content_type: Option<&ContentType>,
+    data: Data,
+    on_duplicate: models::QueryParam<DuplicatePolicy>,
+    user: models::User,
+) -> Result<status::Custom<Json<ImportReport>>, ApiError> {
+    let policy = match on_duplicate {
+        Some(Ok(policy)) => policy,
+        Some(Err(value)) => {
+            return Err(ApiError::bad_request(
+                "invalid_on_duplicate",
+                &format!("on_duplicate must be reject, replace or sum, got {}", value),
+            ))
+        }
+        None => DuplicatePolicy::Reject,
+    };
+    let skillblock =
+        query_owned_skillblock(&conn, &user, block_id).map_err(|error| match error {
+            Error::NotFound => ApiError::not_found("skillblock_not_found", "Skillblock not found"),
+            _ => ApiError::from(error),
+        })?;
+    if skillblock.source != TimeSourceKind::Manual.as_str() {
+        return Err(ApiError::unprocessable(
+            "import_not_allowed",
+            "Time data of skillblock is synced from its time source",
+        ));
+    }
+
+    // Read one byte past limit, so oversized bodies are turned
+    // down rather than cut off partway through a row
+    let mut bytes = Vec::new();
+    data.open()
+        .take(IMPORT_LIMIT + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|_| ApiError::bad_request("invalid_body", "Import body couldn't be read"))?;
+    if bytes.len() as u64 > IMPORT_LIMIT {
+        return Err(ApiError::new(
+            Status::PayloadTooLarge,
+            "import_too_large",
+            &format!("Import body must be at most {} bytes", IMPORT_LIMIT),
+        ));
+    }
+    let body = String::from_utf8(bytes)
+        .map_err(|_| ApiError::bad_request("invalid_body", "Import body must be utf-8 text"))?;
+
+    let (rows, mut errors) = match content_type {
+        Some(kind) if kind.is_json() => parse_json(&body)
+            .map_err(|error| ApiError::bad_request("invalid_json", &error.to_string()))?,
+        _ => parse_csv(&body),
+    };
+
+    // Seconds already on record for each day of skillblock
+    let existing: HashMap<_, _> = query_date_times_desc(&conn, &skillblock)?
+        .into_iter()
+        .map(|(day_date, day_time)| (day_date.date(), day_time))
+        .collect();
+
+    let today = Local::now().date().naive_utc();
+    let plan = plan_import(rows, &existing, policy, today);
+    errors.extend(plan.errors);
+    errors.sort_by_key(|error| error.row);
+
+    // Write all valid rows in one transaction
+    let date_times: Vec<NewDateTime> = plan
+        .inserts
+        .iter()
+        .chain(plan.updates.iter())
+        .map(|(day, seconds)| NewDateTime {
+            block_id: Some(skillblock.block_id),
+            day_time: *seconds,
+            day_date: day.and_hms(0, 0, 0),
+        })
+        .collect();
+    upsert_date_times(&conn, &date_times)?;
+
+    let report = ImportReport {
+        inserted: plan.inserts.len(),
+        updated: plan.updates.len(),
+        errors,
+    };
+
+    // Report 422 if no rows made it into database
+    let status = if report.inserted + report.updated == 0 && !report.errors.is_empty() {
+        Status::UnprocessableEntity
+    } else {
+        Status::Ok
+    };
+
+    Ok(status::Custom(status, Json(report)))
+}
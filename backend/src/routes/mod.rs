@@ -1,4 +1,12 @@
+pub mod account;
+pub mod api_tokens;
 pub mod authentication;
+pub mod entries;
+pub mod export;
+pub mod goals;
 pub mod health;
+pub mod import;
 pub mod index;
+pub mod palettes;
+pub mod share;
 pub mod skillblocks;
@@ -0,0 +1,9 @@
+use crate::palettes::{Palette, PALETTES};
+
+use rocket_contrib::json::Json;
+
+// Serve built in palettes skillblocks can be shaded with
+#[get("/api/palettes")]
+pub fn get_palettes() -> Json<&'static [Palette]> {
+    Json(&PALETTES)
+}
@@ -0,0 +1,378 @@
+use crate::db::models;
+use crate::db::operations::{
+    query_date_times_between, query_shared_skillblock, update_skillblock_share_token,
+    BlockplotDbConn,
+};
+use crate::errors::ApiError;
+use crate::palettes::appearance;
+use crate::render::{render_card_svg, render_grid_svg, render_png};
+use crate::routes::skillblocks::{owned_skillblock, year_param};
+use crate::stats::skillblock_stats;
+use crate::sync::backfill::year_before;
+
+use chrono::{Local, NaiveDate};
+
+use diesel::result::Error;
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use rocket::http::{ContentType, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::content::Content;
+use rocket::response::{self, Responder, Response};
+use rocket_contrib::json::Json;
+
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::iter;
+
+// Length of share tokens. 32 alphanumeric characters
+// are far too many to be guessed or enumerated
+const SHARE_TOKEN_LENGTH: usize = 32;
+
+// Seconds clients and proxies may reuse a shared png before
+// revalidating it against its entity tag
+const SHARED_PNG_MAX_AGE: u32 = 300;
+
+// Render grid of a skillblock owned by user as a standalone svg. Covers
+// a calendar year when given, otherwise the year leading up to today
+#[get("/api/skillblocks/<block_id>/grid.svg?<year>")]
+pub fn get_grid_svg(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    year: models::QueryParam<i32>,
+    user: models::User,
+) -> Result<Content<String>, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    grid_svg(&conn, &skillblock, year_param(year)?)
+}
+
+// Render grid of a shared skillblock. Needs no login, so grids can be
+// embedded in pages that can't run the frontend app
+#[get("/api/share/<token>/grid.svg?<year>")]
+pub fn get_shared_grid_svg(
+    token: String,
+    conn: BlockplotDbConn,
+    year: models::QueryParam<i32>,
+) -> Result<Content<String>, ApiError> {
+    let skillblock = shared_skillblock(&conn, &token)?;
+
+    grid_svg(&conn, &skillblock, year_param(year)?)
+}
+
+// Render grid and stats of a skillblock owned by user as a png download
+#[get("/api/skillblocks/<block_id>/grid.png?<year>")]
+pub fn get_grid_png(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    year: models::QueryParam<i32>,
+    user: models::User,
+) -> Result<Png, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+    let grid = grid_data(&conn, &skillblock, year_param(year)?)?;
+
+    Ok(Png {
+        bytes: grid_png(&skillblock, &grid)?,
+        download: Some(png_filename(&skillblock)),
+        etag: None,
+    })
+}
+
+// Render grid and stats of a shared skillblock as a png image, for
+// tools that only unfurl raster images. Anyone holding the link can
+// hit it, so images are cached by tag of the data they're drawn from
+// and only rendered again once that data changes
+#[get("/api/share/<token>/grid.png?<year>")]
+pub fn get_shared_grid_png(
+    token: String,
+    conn: BlockplotDbConn,
+    year: models::QueryParam<i32>,
+    if_none_match: IfNoneMatch,
+) -> Result<SharedPng, ApiError> {
+    let skillblock = shared_skillblock(&conn, &token)?;
+    let grid = grid_data(&conn, &skillblock, year_param(year)?)?;
+
+    let etag = grid_etag(&skillblock, &grid);
+    if if_none_match.0.as_deref() == Some(etag.as_str()) {
+        return Ok(SharedPng::NotModified(etag));
+    }
+
+    Ok(SharedPng::Image(Png {
+        bytes: grid_png(&skillblock, &grid)?,
+        download: None,
+        etag: Some(etag),
+    }))
+}
+
+// Share grid of a skillblock owned by user under a new token.
+// Sharing again replaces the token, cutting off the old link
+#[post("/api/skillblocks/<block_id>/share")]
+pub fn share_skillblock(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    user: models::User,
+) -> Result<Json<models::ShareLink>, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    let token = generate_share_token();
+    update_skillblock_share_token(&conn, skillblock.block_id, Some(&token))?;
+
+    Ok(Json(models::ShareLink {
+        svg_url: format!("/api/share/{}/grid.svg", token),
+        share_token: token,
+    }))
+}
+
+// Stop sharing grid of a skillblock owned by user
+#[delete("/api/skillblocks/<block_id>/share")]
+pub fn unshare_skillblock(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    user: models::User,
+) -> Result<Status, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+    if skillblock.share_token.is_none() {
+        return Err(ApiError::not_found(
+            "share_not_found",
+            "Skillblock isn't shared",
+        ));
+    }
+
+    update_skillblock_share_token(&conn, skillblock.block_id, None)?;
+
+    Ok(Status::NoContent)
+}
+
+// Fetch skillblock shared under token
+fn shared_skillblock(conn: &BlockplotDbConn, token: &str) -> Result<models::Skillblock, ApiError> {
+    query_shared_skillblock(conn, token).map_err(|error| match error {
+        Error::NotFound => ApiError::not_found("share_not_found", "Shared skillblock not found"),
+        _ => ApiError::from(error),
+    })
+}
+
+// Time data of a skillblock prepared for rendering
+struct GridData {
+    start: NaiveDate,
+    end: NaiveDate,
+    time_data: HashMap<NaiveDate, i32>,
+    appearance: models::Appearance,
+}
+
+// Gather time data of skillblock for requested year. Covers a
+// calendar year when given, otherwise the year leading up to today
+fn grid_data(
+    conn: &BlockplotDbConn,
+    skillblock: &models::Skillblock,
+    year: Option<i32>,
+) -> Result<GridData, ApiError> {
+    let (start, end) = match year {
+        Some(year) => match (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year, 12, 31),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                return Err(ApiError::bad_request(
+                    "invalid_range",
+                    "Year is out of range",
+                ))
+            }
+        },
+        None => {
+            let today = Local::now().date().naive_utc();
+            (year_before(today), today)
+        }
+    };
+
+    let time_data: HashMap<NaiveDate, i32> = query_date_times_between(
+        conn,
+        skillblock,
+        Some(start.and_hms(0, 0, 0)),
+        Some(end.and_hms(0, 0, 0)),
+    )?
+    .into_iter()
+    .map(|(day_date, day_time)| (day_date.date(), day_time))
+    .collect();
+    let minutes: Vec<i32> = time_data.values().map(|day_time| day_time / 60).collect();
+
+    Ok(GridData {
+        start,
+        end,
+        appearance: appearance(skillblock, &minutes),
+        time_data,
+    })
+}
+
+// Render grid of skillblock as svg
+fn grid_svg(
+    conn: &BlockplotDbConn,
+    skillblock: &models::Skillblock,
+    year: Option<i32>,
+) -> Result<Content<String>, ApiError> {
+    let grid = grid_data(conn, skillblock, year)?;
+    let svg = render_grid_svg(
+        &skillblock.skill_name,
+        grid.start,
+        grid.end,
+        &grid.time_data,
+        &grid.appearance,
+    );
+
+    Ok(Content(ContentType::SVG, svg))
+}
+
+// Render grid of skillblock along with its stats row as png
+fn grid_png(skillblock: &models::Skillblock, grid: &GridData) -> Result<Vec<u8>, ApiError> {
+    let date_times: Vec<(NaiveDate, i32)> = grid
+        .time_data
+        .iter()
+        .map(|(day_date, day_time)| (*day_date, *day_time))
+        .collect();
+    let stats = skillblock_stats(&date_times, stats_end(grid));
+
+    let svg = render_card_svg(
+        &skillblock.skill_name,
+        grid.start,
+        grid.end,
+        &grid.time_data,
+        &grid.appearance,
+        &stats,
+    );
+
+    render_png(&svg).map_err(|error| {
+        println!("Error rendering png of skillblock: {}", error);
+        ApiError::internal()
+    })
+}
+
+// Last day streaks of a grid are counted up to. Streaks
+// of a past year are counted up to its last day
+fn stats_end(grid: &GridData) -> NaiveDate {
+    let today = Local::now().date().naive_utc();
+
+    grid.end.min(today)
+}
+
+// Entity tag of a rendered grid, hashed from everything drawn on it.
+// Stats depend on today's date, so tags turn over daily as well
+fn grid_etag(skillblock: &models::Skillblock, grid: &GridData) -> String {
+    let mut days: Vec<(&NaiveDate, &i32)> = grid.time_data.iter().collect();
+    days.sort();
+
+    let mut key = format!(
+        "{}|{}|{}|{}|{}|{}|{}",
+        skillblock.skill_name,
+        grid.start,
+        grid.end,
+        stats_end(grid),
+        grid.appearance.palette,
+        grid.appearance.intensity_scale,
+        grid.appearance.colors.join(","),
+    );
+    for threshold in &grid.appearance.thresholds {
+        let _ = write!(key, "|{}", threshold);
+    }
+    for (day_date, day_time) in days {
+        let _ = write!(key, "|{}={}", day_date, day_time);
+    }
+
+    format!("\"{:x}\"", Sha256::digest(key.as_bytes()))
+}
+
+// Entity tag a client sent back to revalidate a cached image
+pub struct IfNoneMatch(Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let etag = request.headers().get_one("If-None-Match").map(String::from);
+
+        request::Outcome::Success(IfNoneMatch(etag))
+    }
+}
+
+// Png image response. Downloads are saved under filename by
+// browsers, other images are shown inline, such as in chat unfurls
+pub struct Png {
+    bytes: Vec<u8>,
+    download: Option<String>,
+    etag: Option<String>,
+}
+
+impl<'r> Responder<'r> for Png {
+    fn respond_to(self, _request: &Request) -> response::Result<'r> {
+        let mut response = Response::build();
+        response
+            .header(ContentType::PNG)
+            .sized_body(Cursor::new(self.bytes));
+        if let Some(filename) = self.download {
+            response.raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", filename),
+            );
+        }
+        if let Some(etag) = self.etag {
+            response
+                .raw_header("ETag", etag)
+                .raw_header("Cache-Control", cache_control());
+        }
+
+        response.ok()
+    }
+}
+
+// Shared png response. Clients already holding the current
+// image are answered with an empty 304 Not Modified
+pub enum SharedPng {
+    Image(Png),
+    NotModified(String),
+}
+
+impl<'r> Responder<'r> for SharedPng {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        match self {
+            SharedPng::Image(png) => png.respond_to(request),
+            SharedPng::NotModified(etag) => Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", etag)
+                .raw_header("Cache-Control", cache_control())
+                .ok(),
+        }
+    }
+}
+
+fn cache_control() -> String {
+    format!("public, max-age={}", SHARED_PNG_MAX_AGE)
+}
+
+// Name png downloads after skillblock, keeping only characters safe in a filename
+fn png_filename(skillblock: &models::Skillblock) -> String {
+    let name: String = skillblock
+        .skill_name
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    format!("{}-grid.png", name.trim_matches('-'))
+}
+
+fn generate_share_token() -> String {
+    let mut rng = thread_rng();
+
+    iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(SHARE_TOKEN_LENGTH)
+        .collect()
+}
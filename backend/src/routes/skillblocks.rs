@@ -1,360 +1,206 @@
-use crate::auth::auth0::SessionDB;
+use crate::auth::auth0::{Session, SessionDB};
 use crate::db::models;
-use crate::db::models::NewDateTime;
-use crate::db::operations::add_date_time;
 use crate::db::operations::{
-    add_user_key, batch_add_date_times, create_skillblock, query_date_times_desc,
-    query_skillblocks, update_block_count, update_blocks_last_fetched, update_date_time,
-    BlockplotDbConn,
+    add_user_key, add_user_toggl_key, add_user_wakatime_key, create_skillblock, delete_skillblock,
+    query_date_times_between, query_owned_skillblock, query_skillblocks, refresh_block_count,
+    reset_skillblock_time_data, update_skillblock, BlockplotDbConn,
 };
+use crate::errors::ApiError;
+use crate::palettes::{appearance, palette, validate_thresholds, IntensityScale};
+use crate::sources::TimeSourceKind;
+use crate::stats::{skillblock_stats, SkillblockStats};
+use crate::sync::{SyncHandle, SyncStatus};
 
-use chrono::prelude::*;
-use chrono::Duration;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+
+use diesel::result::Error;
+use diesel::Connection;
 
 use rocket::http::{Cookies, Status};
 use rocket::request::Form;
-use rocket::response::{Flash, Redirect};
+use rocket::response::{status, Flash, Redirect};
 use rocket::State;
 use rocket_contrib::json::Json;
 
-use rusty_rescuetime::analytic_data::{AnalyticData, QueryKind};
-use rusty_rescuetime::parameters::Parameters;
-use rusty_rescuetime::parameters::PerspectiveOptions::Interval;
-use rusty_rescuetime::parameters::ResolutionOptions::Day;
-use rusty_rescuetime::parameters::RestrictData::{Date, Thing};
-use rusty_rescuetime::parameters::RestrictOptions::{Category, Overview};
-
-use std::collections::HashMap;
-
-// Route handler fetches user skillblock information from database,
-// fetches timedata from RescueTime api,
-// and serves processed information to frontend
-#[get("/api/skillblocks")]
+// Route handler fetches user skillblock information from database
+// and serves processed information to frontend. Time data is kept
+// up to date by the background sync worker, so only postgres is read here.
+// Optional from and to dates (YYYY-MM-DD) limit the time data returned,
+// or a year can be given to get time data of a single calendar year
+#[get("/api/skillblocks?<from>&<to>&<year>")]
 pub fn get_skillblocks(
     conn: BlockplotDbConn,
+    from: models::QueryParam<models::DayDate>,
+    to: models::QueryParam<models::DayDate>,
+    year: models::QueryParam<i32>,
     user: models::User,
-) -> Result<Json<models::TimeWrapper>, Status> {
-    // Check user for RescueTime api key.
-    // Return 404 status if not found
-    //TODO: Return more appropriate status code here
-    if !user.key_present {
-        return Err(Status::NotFound);
-    }
-
-    // TODO: Should handle the error here
-    let categories = query_skillblocks(&conn, &user).unwrap();
+) -> Result<Json<models::TimeWrapper>, ApiError> {
+    let range = date_range(from, to, year)?;
+    let categories = query_skillblocks(&conn, &user)?;
 
+    // Tell users yet to hand over any time source credentials
+    // apart from users that simply have no skillblocks yet
     if categories.len() < 1 {
-        return Err(Status::NotFound);
+        let credentials_stored = [
+            TimeSourceKind::RescueTime,
+            TimeSourceKind::Toggl,
+            TimeSourceKind::WakaTime,
+        ]
+        .iter()
+        .any(|source| user.has_source_key(*source));
+        return Err(if credentials_stored {
+            ApiError::not_found("no_skillblocks", "User has no skillblocks")
+        } else {
+            ApiError::not_found("missing_api_key", "No time source api key found for user")
+        });
     }
 
     // Vector holds datastructures to be passed back to frontend
     let mut time_vec = Vec::new();
 
-    let api_key = user.api_key.unwrap();
-    let format = String::from("json");
+    for skillblock in categories {
+        time_vec.push(time_data_for(&conn, skillblock, range)?);
+    }
 
-    // Setup current year date
-    let current_date = Local::now().date().naive_utc();
-    let (current_year, current_month, current_day) = (
-        current_date.year(),
-        current_date.month(),
-        current_date.day(),
-    );
-    let year_end = NaiveDate::from_ymd(current_year, current_month, current_day);
+    let wrapped_json = models::TimeWrapper { data: time_vec };
 
-    // loop through gathered database records and use information to make
-    // query calls to rescuetime api for time data
-    for skillblock in categories {
-        let records_present = query_date_times_desc(&conn, &skillblock);
-        match records_present {
-            Ok(date_times) => {
-                // Check if any records already exist
-                if date_times.len() < 1 {
-                    // Setup previous year date
-                    let next_date = current_date.succ();
-                    let (prev_year, prev_month, prev_day) =
-                        (next_date.year() - 1, next_date.month(), next_date.day());
-
-                    //TODO: Currently pulling in data from less than a year. Figure out how to query data for a full year
-                    let year_start = NaiveDate::from_ymd(prev_year, prev_month, prev_day);
-
-                    let query_parameters;
-
-                    // Check for needed type of restrict_kind parameter
-                    if skillblock.offline_category {
-                        query_parameters = Parameters::new(
-                            Some(Interval),
-                            Some(Day),
-                            //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                            Some(Date(year_start.to_string(), year_end.to_string())),
-                            Some(Category),
-                            Some(Thing(skillblock.category.to_string())),
-                            None,
-                        );
-                    } else {
-                        query_parameters = Parameters::new(
-                            Some(Interval),
-                            Some(Day),
-                            //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                            Some(Date(year_start.to_string(), year_end.to_string())),
-                            Some(Overview),
-                            Some(Thing(skillblock.category.to_string())),
-                            None,
-                        );
-                    }
-
-                    let payload =
-                        AnalyticData::fetch(&api_key, query_parameters, format.clone()).unwrap();
-
-                    let mut response = models::TimeData {
-                        category: skillblock.category,
-                        skill_name: skillblock.skill_name,
-                        skill_description: skillblock.description,
-                        time_data: HashMap::new(),
-                    };
-
-                    // Create hash key/values and sum total time for given category
-                    for query in payload.rows {
-                        if let QueryKind::SizeSixString(value) = query {
-                            if let Some(x) = response.time_data.get_mut(&value.perspective) {
-                                *x += value.time_spent;
-                            } else {
-                                response
-                                    .time_data
-                                    .insert(value.perspective, value.time_spent);
-                            }
-                        }
-                    }
-
-                    let mut time_data_store = Vec::new();
-                    for (key, val) in response.time_data.iter() {
-                        let db_date_time = NewDateTime {
-                            block_id: Some(skillblock.block_id),
-                            day_time: *val,
-                            day_date: *key,
-                        };
-                        time_data_store.push(db_date_time);
-                    }
-
-                    match batch_add_date_times(&conn, &time_data_store) {
-                        Ok(rows) => println!("Successfully added {} rows to date database", rows),
-                        Err(error) => {
-                            println!("Error saving date data to db: {}", error);
-                            return Err(Status::InternalServerError);
-                        }
-                    }
-
-                    time_vec.push(response);
-                } else {
-                    // Setup current date, and last known date blocks were
-                    // fetched from the database
-                    let current_nd = Local::now().date().naive_utc();
-                    let last_fetched = user.blocks_last_fetched.date();
-
-                    // Update time data of last known login date
-                    let mut query_parameters;
-                    if skillblock.offline_category {
-                        query_parameters = Parameters::new(
-                            Some(Interval),
-                            Some(Day),
-                            //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                            Some(Date(last_fetched.to_string(), last_fetched.to_string())),
-                            Some(Category),
-                            Some(Thing(skillblock.category.to_string())),
-                            None,
-                        );
-                    } else {
-                        query_parameters = Parameters::new(
-                            Some(Interval),
-                            Some(Day),
-                            //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                            Some(Date(last_fetched.to_string(), last_fetched.to_string())),
-                            Some(Overview),
-                            Some(Thing(skillblock.category.to_string())),
-                            None,
-                        );
-                    }
-
-                    let payload =
-                        AnalyticData::fetch(&api_key, query_parameters, format.clone()).unwrap();
-
-                    let mut last_date_data: (NaiveDateTime, i32) =
-                        (last_fetched.and_hms(0, 0, 0), 0);
-
-                    // Recalculate time total of last known login date
-                    for query in payload.rows {
-                        if let QueryKind::SizeSixString(value) = query {
-                            last_date_data.1 += value.time_spent;
-                        }
-                    }
-
-                    // Update database record with newly calculated date data of
-                    // last known login date. If database record doesn't exist,
-                    // create a record and insert into database
-                    match update_date_time(
-                        &conn,
-                        skillblock.block_id,
-                        last_date_data.0,
-                        last_date_data.1,
-                    ) {
-                        Ok(row) => {
-                            println!("Successfully updated {} row!", row);
-                            if row == 0 {
-                                let new_date_time = NewDateTime {
-                                    block_id: Some(skillblock.block_id),
-                                    day_time: last_date_data.1,
-                                    day_date: last_date_data.0,
-                                };
-                                match add_date_time(&conn, new_date_time) {
-                                    Ok(row) => {
-                                        println!("Successfully added {} row to database", row)
-                                    }
-                                    Err(error) => {
-                                        println!("Error saving date data to db: {}", error)
-                                    }
-                                }
-                            }
-                        }
-                        Err(error) => {
-                            println!("Error updating date data in db: {}", error);
-                            return Err(Status::InternalServerError);
-                        }
-                    }
-
-                    // Check for elapsed time between last known block
-                    // fetch date and current date
-                    if last_fetched != current_nd {
-                        // Generate day date that is one day older
-                        // than last known block fetch date
-                        let end_date = last_fetched + Duration::days(1);
-
-                        // Query ResueTime Api for data spanning length
-                        // of elapsed time between last known block
-                        // fetch and current date
-                        if skillblock.offline_category {
-                            query_parameters = Parameters::new(
-                                Some(Interval),
-                                Some(Day),
-                                //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                                Some(Date(end_date.to_string(), current_date.to_string())),
-                                Some(Category),
-                                Some(Thing(skillblock.category.to_string())),
-                                None,
-                            );
-                        } else {
-                            query_parameters = Parameters::new(
-                                Some(Interval),
-                                Some(Day),
-                                //TODO: Currently cloning Date's fields here. Figure out if instead lifetime identifier should be included on Parameter struct
-                                Some(Date(end_date.to_string(), current_date.to_string())),
-                                Some(Overview),
-                                Some(Thing(skillblock.category.to_string())),
-                                None,
-                            );
-                        }
-
-                        let payload =
-                            AnalyticData::fetch(&api_key, query_parameters, format.clone())
-                                .unwrap();
-
-                        let mut data = models::TimeData {
-                            category: skillblock.category,
-                            skill_name: skillblock.skill_name,
-                            skill_description: skillblock.description,
-                            time_data: HashMap::new(),
-                        };
-
-                        // Create hash key/values and sum total time for given category
-                        for query in payload.rows {
-                            if let QueryKind::SizeSixString(value) = query {
-                                if let Some(x) = data.time_data.get_mut(&value.perspective) {
-                                    *x += value.time_spent;
-                                } else {
-                                    data.time_data.insert(value.perspective, value.time_spent);
-                                }
-                            }
-                        }
-
-                        // Store newly calcuated time data in vector
-                        // bound for postgres database
-                        let mut time_data_store = Vec::new();
-                        for (key, val) in data.time_data.iter() {
-                            let db_date_time = NewDateTime {
-                                block_id: Some(skillblock.block_id),
-                                day_time: *val,
-                                day_date: *key,
-                            };
-                            time_data_store.push(db_date_time);
-                        }
-
-                        // Add newly calculated time data to postgres database
-                        match batch_add_date_times(&conn, &time_data_store) {
-                            Ok(rows) => println!("Successfully added {} rows to database", rows),
-                            Err(error) => {
-                                println!("Error saving date data to db: {}", error);
-                                return Err(Status::InternalServerError);
-                            }
-                        }
-
-                        // Insert previous time data records
-                        // gathered from postgres database
-                        for dt in date_times {
-                            data.time_data.insert(dt.0, dt.1);
-                        }
-
-                        // Insert newly updated record created from last
-                        // known block fetch date
-                        if let Some(x) = data.time_data.get_mut(&last_date_data.0) {
-                            *x = last_date_data.1;
-                        } else {
-                            data.time_data.insert(last_date_data.0, last_date_data.1);
-                        }
-
-                        time_vec.push(data);
-                    } else {
-                        let mut data = models::TimeData {
-                            category: skillblock.category,
-                            skill_name: skillblock.skill_name,
-                            skill_description: skillblock.description,
-                            time_data: HashMap::new(),
-                        };
-
-                        // Insert previous time data records
-                        // gathered from postgres database
-                        for dt in date_times {
-                            data.time_data.insert(dt.0, dt.1);
-                        }
-
-                        // Insert newly updated record created from last
-                        // known block fetch date
-                        if let Some(x) = data.time_data.get_mut(&last_date_data.0) {
-                            *x = last_date_data.1;
-                        } else {
-                            data.time_data.insert(last_date_data.0, last_date_data.1);
-                        }
-
-                        time_vec.push(data);
-                    }
-                }
-            }
-            Err(error) => {
-                println!("Error fetching date time records: {}", error);
-                return Err(Status::InternalServerError);
-            }
+    Ok(Json(wrapped_json))
+}
+
+// Serve a single skillblock owned by user along with its time data.
+// Takes the same optional query parameters as get_skillblocks
+#[get("/api/skillblocks/<block_id>?<from>&<to>&<year>")]
+pub fn get_skillblock(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    from: models::QueryParam<models::DayDate>,
+    to: models::QueryParam<models::DayDate>,
+    year: models::QueryParam<i32>,
+    user: models::User,
+) -> Result<Json<models::TimeData>, ApiError> {
+    let range = date_range(from, to, year)?;
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    Ok(Json(time_data_for(&conn, skillblock, range)?))
+}
+
+// Serve streaks, averages and totals of a single skillblock owned by user.
+// Takes the same optional query parameters as get_skillblocks
+#[get("/api/skillblocks/<block_id>/stats?<from>&<to>&<year>")]
+pub fn get_skillblock_stats(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    from: models::QueryParam<models::DayDate>,
+    to: models::QueryParam<models::DayDate>,
+    year: models::QueryParam<i32>,
+    user: models::User,
+) -> Result<Json<SkillblockStats>, ApiError> {
+    let (from, to) = date_range(from, to, year)?;
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+    let date_times: Vec<(NaiveDate, i32)> = query_date_times_between(&conn, &skillblock, from, to)?
+        .into_iter()
+        .map(|(day_date, day_time)| (day_date.date(), day_time))
+        .collect();
+
+    // Days are counted on the same clock the sync worker and
+    // manual entries use, so streaks line up with stored days
+    let today = Local::now().date().naive_utc();
+
+    Ok(Json(skillblock_stats(&date_times, today)))
+}
+
+// Inclusive date range limiting time data served, as day_date timestamps
+pub(crate) type DateRange = (Option<NaiveDateTime>, Option<NaiveDateTime>);
+
+// Check optional from and to dates, or a calendar year, describe a valid range
+pub(crate) fn date_range(
+    from: models::QueryParam<models::DayDate>,
+    to: models::QueryParam<models::DayDate>,
+    year: models::QueryParam<i32>,
+) -> Result<DateRange, ApiError> {
+    let from = day_param(from, "from")?;
+    let to = day_param(to, "to")?;
+    let year = year_param(year)?;
+    let (from, to) = match year {
+        Some(_) if from.is_some() || to.is_some() => {
+            return Err(ApiError::bad_request(
+                "invalid_range",
+                "Year can't be combined with from or to dates",
+            ));
+        }
+        Some(year) => {
+            let start = NaiveDate::from_ymd_opt(year, 1, 1)
+                .ok_or_else(|| ApiError::bad_request("invalid_range", "Year is out of range"))?;
+            (Some(start), NaiveDate::from_ymd_opt(year, 12, 31))
+        }
+        None => (from, to),
+    };
+
+    if let (Some(start), Some(end)) = (from, to) {
+        if start > end {
+            return Err(ApiError::bad_request(
+                "invalid_range",
+                "From date must not be after to date",
+            ));
         }
     }
 
-    let wrapped_json = models::TimeWrapper { data: time_vec };
+    Ok((
+        from.map(|day| day.and_hms(0, 0, 0)),
+        to.map(|day| day.and_hms(0, 0, 0)),
+    ))
+}
 
-    //TODO: Should rename schema to differentiate between database login and
-    // website login
-    // Update database record that keeps track of last date skillblocks were fetched
-    update_blocks_last_fetched(&conn, user.auth_id)
-        .map_err(|_| Status::InternalServerError)
-        .unwrap();
+// Unwrap date query parameter, turning down values that aren't YYYY-MM-DD dates
+fn day_param(
+    day: models::QueryParam<models::DayDate>,
+    name: &str,
+) -> Result<Option<NaiveDate>, ApiError> {
+    match day {
+        Some(Ok(day)) => Ok(Some(day.0)),
+        Some(Err(value)) => Err(ApiError::bad_request(
+            "invalid_date",
+            &format!("{} must be a YYYY-MM-DD date, got {}", name, value),
+        )),
+        None => Ok(None),
+    }
+}
 
-    Ok(Json(wrapped_json))
+// Unwrap year query parameter, turning down values that aren't a year
+pub(crate) fn year_param(year: models::QueryParam<i32>) -> Result<Option<i32>, ApiError> {
+    match year {
+        Some(Ok(year)) => Ok(Some(year)),
+        Some(Err(value)) => Err(ApiError::bad_request(
+            "invalid_range",
+            &format!("Year must be a number, got {}", value),
+        )),
+        None => Ok(None),
+    }
+}
+
+// Gather time data of skillblock within date range into payload served to frontend
+fn time_data_for(
+    conn: &BlockplotDbConn,
+    skillblock: models::Skillblock,
+    (from, to): DateRange,
+) -> Result<models::TimeData, ApiError> {
+    let date_times = query_date_times_between(conn, &skillblock, from, to)?;
+    let minutes: Vec<i32> = date_times
+        .iter()
+        .map(|(_, day_time)| day_time / 60)
+        .collect();
+    let appearance = appearance(&skillblock, &minutes);
+
+    Ok(models::TimeData {
+        block_id: skillblock.block_id,
+        category: skillblock.category,
+        skill_name: skillblock.skill_name,
+        skill_description: skillblock.description,
+        time_data: date_times.into_iter().collect(),
+        last_synced_at: skillblock.last_synced_at,
+        sync_status: skillblock.sync_status,
+        appearance,
+    })
 }
 
 // Prototype handler meant to handle fowards due to User RequestGuard failures
@@ -366,86 +212,245 @@ pub fn get_skillblocks_redirect() -> Flash<Redirect> {
 // Handle form post request and store form data into database
 #[post("/api/new_skillblock", data = "<form_data>")]
 pub fn new_skillblock(
-    user: models::User,
+    user: models::SessionUser,
     conn: BlockplotDbConn,
     cookies: Cookies,
     form_data: Form<models::FormData>,
     session_db: State<SessionDB>,
-) -> Result<Redirect, Status> {
-    if user.block_count > 3 {
-        return Err(Status::Forbidden);
+    sync_handle: State<SyncHandle>,
+) -> Result<Redirect, ApiError> {
+    match create_new_skillblock(
+        &conn,
+        &user.0,
+        &form_data,
+        &cookies,
+        &session_db,
+        &sync_handle,
+    ) {
+        Ok(_) => Ok(Redirect::to("http://localhost:8080/user")),
+        // Form posts report missing credentials as forbidden
+        // and any other invalid field as a bad request
+        Err(CreateError::Invalid(errors)) => {
+            let status = if errors
+                .iter()
+                .any(|error| error.code == "missing_credentials")
+            {
+                Status::Forbidden
+            } else {
+                Status::BadRequest
+            };
+            Err(
+                ApiError::new(status, "validation_failed", "Skillblock is invalid")
+                    .with_details(errors),
+            )
+        }
+        Err(error) => Err(ApiError::from(error)),
+    }
+}
+
+// Handle json post request and store skillblock into database. Responds
+// with created skillblock, or with every problem found in the request
+#[post("/api/v1/skillblocks", format = "json", data = "<data>")]
+pub fn create_skillblock_json(
+    user: models::SessionUser,
+    conn: BlockplotDbConn,
+    cookies: Cookies,
+    data: Json<models::FormData>,
+    session_db: State<SessionDB>,
+    sync_handle: State<SyncHandle>,
+) -> Result<status::Created<Json<models::Skillblock>>, ApiError> {
+    let skillblock =
+        create_new_skillblock(&conn, &user.0, &data, &cookies, &session_db, &sync_handle)?;
+    let location = format!("/api/skillblocks/{}", skillblock.block_id);
+
+    Ok(status::Created(location, Some(Json(skillblock))))
+}
+
+// Reasons a skillblock create request can be turned down
+enum CreateError {
+    LimitReached,
+    Invalid(Vec<models::FieldError>),
+    Internal,
+}
+
+impl From<CreateError> for ApiError {
+    fn from(error: CreateError) -> Self {
+        match error {
+            CreateError::LimitReached => {
+                ApiError::forbidden("skillblock_limit_reached", "Skillblock limit reached")
+            }
+            CreateError::Invalid(errors) => {
+                ApiError::unprocessable("validation_failed", "Skillblock is invalid")
+                    .with_details(errors)
+            }
+            CreateError::Internal => ApiError::internal(),
+        }
+    }
+}
+
+fn field_error(field: &str, code: &str, message: &str) -> models::FieldError {
+    models::FieldError {
+        field: field.to_string(),
+        code: code.to_string(),
+        message: message.to_string(),
     }
-    if !user.key_present {
-        match &form_data.api_key {
-            Some(key) => {
+}
+
+// Check value was sent and isn't blank
+fn provided(value: &Option<String>) -> bool {
+    value
+        .as_ref()
+        .map_or(false, |value| !value.trim().is_empty())
+}
+
+// Check create request for problems. Returns time source
+// of new skillblock if none are found
+fn validate_new_skillblock(
+    user: &models::User,
+    data: &models::FormData,
+) -> Result<TimeSourceKind, Vec<models::FieldError>> {
+    let mut errors = Vec::new();
+
+    let required = [
+        ("category", &data.category),
+        ("skill_name", &data.skill_name),
+        ("description", &data.description),
+    ];
+    for (field, value) in required.iter() {
+        if value.trim().is_empty() {
+            errors.push(field_error(field, "required", "Field can't be blank"));
+        }
+    }
+
+    let source = match &data.source {
+        Some(value) => match value.parse::<TimeSourceKind>() {
+            Ok(source) => Some(source),
+            Err(error) => {
+                errors.push(field_error("source", "invalid", &error.to_string()));
+                None
+            }
+        },
+        None => Some(TimeSourceKind::RescueTime),
+    };
+
+    // Time sources need credentials, either stored earlier or sent now
+    let missing_credentials = match source {
+        Some(source) if !user.has_source_key(source) => match source {
+            TimeSourceKind::RescueTime if !provided(&data.api_key) => Some("api_key"),
+            TimeSourceKind::Toggl if !provided(&data.toggl_api_key) => Some("toggl_api_key"),
+            TimeSourceKind::WakaTime if !provided(&data.wakatime_api_key) => {
+                Some("wakatime_api_key")
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(field) = missing_credentials {
+        errors.push(field_error(
+            field,
+            "missing_credentials",
+            "Api key is required for this time source",
+        ));
+    }
+
+    match source {
+        Some(source) if errors.is_empty() => Ok(source),
+        _ => Err(errors),
+    }
+}
+
+// Store credentials sent along with create request for the selected time source
+fn store_credentials(
+    conn: &BlockplotDbConn,
+    user: &models::User,
+    data: &models::FormData,
+    source: TimeSourceKind,
+    cookies: &Cookies,
+    session_db: &SessionDB,
+) -> Result<(), Error> {
+    match source {
+        TimeSourceKind::RescueTime => {
+            if let (false, Some(key)) = (user.key_present, &data.api_key) {
                 // Consider updating db query operation to remove use of string copy
-                let query_result = add_user_key(&conn, user.auth_id.to_string(), &key);
-                match query_result {
-                    Ok(result) => println!("Successfully updated user key! {:?}", result),
-                    Err(error) => {
-                        println!("Error updating user key! {}", error);
-                        return Err(Status::Forbidden);
-                    }
-                }
+                add_user_key(conn, user.auth_id.to_string(), key)?;
 
                 // Update session state record to reflect
                 // addition of RescueTime api key
-                let session_cookie: Option<String> = cookies
-                    .get("session")
-                    .and_then(|cookie| cookie.value().parse().ok());
-                if let Some(session_id) = session_cookie {
-                    let mut session_hashmap = session_db.0.get_mut(&session_id).unwrap();
-                    match *session_hashmap {
-                        Some(ref mut session) => {
-                            session.key_present = true;
-                        }
-                        None => {
-                            println!("Error updating key_present session record in backend");
-                        }
-                    }
-                }
+                update_session(conn, cookies, session_db, |session| {
+                    session.key_present = true
+                });
             }
-            None => {
-                println!("Issue with api key from form");
-                return Err(Status::Forbidden);
+        }
+        TimeSourceKind::Toggl => {
+            if let Some(key) = data.toggl_api_key.as_ref().filter(|key| !key.is_empty()) {
+                add_user_toggl_key(conn, user.auth_id.to_string(), key)?;
             }
         }
+        TimeSourceKind::WakaTime => {
+            if let Some(key) = data.wakatime_api_key.as_ref().filter(|key| !key.is_empty()) {
+                add_user_wakatime_key(conn, user.auth_id.to_string(), key)?;
+            }
+        }
+        // Manual skillblocks need no credentials
+        TimeSourceKind::Manual => {}
+    }
+
+    Ok(())
+}
+
+// Validate create request, store any credentials it carries and create
+// skillblock. Shared by the form and json endpoints
+fn create_new_skillblock(
+    conn: &BlockplotDbConn,
+    user: &models::User,
+    data: &models::FormData,
+    cookies: &Cookies,
+    session_db: &SessionDB,
+    sync_handle: &SyncHandle,
+) -> Result<models::Skillblock, CreateError> {
+    if user.block_count > 3 {
+        return Err(CreateError::LimitReached);
     }
+    let source = validate_new_skillblock(user, data).map_err(CreateError::Invalid)?;
+
+    store_credentials(conn, user, data, source, cookies, session_db).map_err(|error| {
+        println!("Error updating user key! {}", error);
+        CreateError::Internal
+    })?;
 
     let db_skillblock = models::NewSkillblock {
         user_id: Some(user.user_id),
-        category: form_data.category.to_string(),
-        offline_category: form_data.offline_category,
-        skill_description: form_data.description.to_string(),
-        skill_name: form_data.skill_name.to_string(),
+        category: data.category.to_string(),
+        offline_category: data.offline_category,
+        skill_description: data.description.to_string(),
+        skill_name: data.skill_name.to_string(),
+        source: source.as_str().to_string(),
+        sync_status: match source {
+            TimeSourceKind::Manual => SyncStatus::Manual,
+            _ => SyncStatus::Pending,
+        }
+        .as_str()
+        .to_string(),
     };
 
-    create_skillblock(&conn, db_skillblock);
+    let skillblock = create_skillblock(conn, db_skillblock).map_err(|error| {
+        println!("Error saving skillblock to db: {}", error);
+        CreateError::Internal
+    })?;
 
-    // Consider updating db query operation to remove use of string copy
-    match update_block_count(&conn, user.block_count, user.auth_id.to_string()) {
-        Ok(result) => println!("User block count successfully updated! {}", result),
+    // Update user and session state records to reflect
+    // new addition of a skillblock
+    match refresh_block_count(conn, user) {
+        Ok(count) => update_session(conn, cookies, session_db, |session| {
+            session.block_count = count
+        }),
         Err(error) => println!("Error updating user block count :( {}", error),
     }
 
-    // Update session state record to reflect
-    // new addition of a skillblock
-    let session_cookie: Option<String> = cookies
-        .get("session")
-        .and_then(|cookie| cookie.value().parse().ok());
-    if let Some(session_id) = session_cookie {
-        let mut session_hashmap = session_db.0.get_mut(&session_id).unwrap();
-        match *session_hashmap {
-            Some(ref mut session) => {
-                session.block_count += 1;
-            }
-            None => {
-                println!("Error updating block_count session record in backend");
-            }
-        }
-    }
+    // Pull time data of new skillblock without waiting on next sync interval
+    sync_handle.request_sync(user.auth_id.to_string());
 
-    Ok(Redirect::to("http://localhost:8080/user"))
+    Ok(skillblock)
 }
 
 //TODO: Replace with a better redirect handler
@@ -453,3 +458,150 @@ pub fn new_skillblock(
 pub fn new_skillblock_redirect() -> Status {
     Status::Unauthorized
 }
+
+// Edit name, description, category or grid shading of a skillblock owned by user.
+// Time data of a synced skillblock is dropped and fetched again
+// when its category changes, since stored data no longer applies
+#[put("/api/skillblocks/<block_id>", format = "json", data = "<changes>")]
+pub fn edit_skillblock(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    changes: Json<models::SkillblockChanges>,
+    sync_handle: State<SyncHandle>,
+    user: models::User,
+) -> Result<Json<models::Skillblock>, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    let text_fields = [&changes.category, &changes.skill_name, &changes.description];
+    let blank_field = text_fields
+        .iter()
+        .any(|field| matches!(field, Some(value) if value.trim().is_empty()));
+    let no_changes = text_fields.iter().all(|field| field.is_none())
+        && changes.offline_category.is_none()
+        && changes.palette.is_none()
+        && changes.intensity_scale.is_none()
+        && changes.thresholds.is_none();
+    if blank_field {
+        return Err(ApiError::unprocessable(
+            "blank_field",
+            "Fields can't be changed to blank values",
+        ));
+    }
+    if no_changes {
+        return Err(ApiError::unprocessable(
+            "no_changes",
+            "Request doesn't change any field",
+        ));
+    }
+    validate_appearance(&changes)?;
+
+    let category_changed = changes
+        .category
+        .as_ref()
+        .map_or(false, |category| *category != skillblock.category)
+        || changes
+            .offline_category
+            .map_or(false, |offline| offline != skillblock.offline_category);
+    let resync = category_changed && skillblock.source != TimeSourceKind::Manual.as_str();
+
+    let updated = conn.transaction::<_, Error, _>(|| {
+        if resync {
+            reset_skillblock_time_data(&conn, skillblock.block_id)?;
+        }
+
+        update_skillblock(&conn, skillblock.block_id, &changes)
+    })?;
+
+    if resync {
+        sync_handle.request_sync(user.auth_id.to_string());
+    }
+
+    Ok(Json(updated))
+}
+
+// Check palette, intensity scale and thresholds of edit request are supported
+fn validate_appearance(changes: &models::SkillblockChanges) -> Result<(), ApiError> {
+    if let Some(name) = &changes.palette {
+        if palette(name).is_none() {
+            return Err(ApiError::unprocessable(
+                "unknown_palette",
+                &format!("Palette {} doesn't exist", name),
+            ));
+        }
+    }
+    if let Some(scale) = &changes.intensity_scale {
+        if scale.parse::<IntensityScale>().is_err() {
+            return Err(ApiError::unprocessable(
+                "unknown_intensity_scale",
+                "Intensity scale must be fixed or quantile",
+            ));
+        }
+    }
+    if let Some(thresholds) = &changes.thresholds {
+        validate_thresholds(thresholds)
+            .map_err(|message| ApiError::unprocessable("invalid_thresholds", &message))?;
+    }
+
+    Ok(())
+}
+
+// Delete skillblock owned by user along with all of its time data
+#[delete("/api/skillblocks/<block_id>")]
+pub fn remove_skillblock(
+    block_id: i32,
+    conn: BlockplotDbConn,
+    cookies: Cookies,
+    session_db: State<SessionDB>,
+    user: models::User,
+) -> Result<Status, ApiError> {
+    let skillblock = owned_skillblock(&conn, &user, block_id)?;
+
+    let count = conn.transaction::<_, Error, _>(|| {
+        delete_skillblock(&conn, skillblock.block_id)?;
+        refresh_block_count(&conn, &user)
+    })?;
+
+    update_session(&conn, &cookies, &session_db, |session| {
+        session.block_count = count
+    });
+
+    Ok(Status::NoContent)
+}
+
+// Fetch skillblock owned by user. Skillblocks belonging
+// to other users are reported as not found
+pub(crate) fn owned_skillblock(
+    conn: &BlockplotDbConn,
+    user: &models::User,
+    block_id: i32,
+) -> Result<models::Skillblock, ApiError> {
+    query_owned_skillblock(conn, user, block_id).map_err(|error| match error {
+        Error::NotFound => ApiError::not_found("skillblock_not_found", "Skillblock not found"),
+        _ => ApiError::from(error),
+    })
+}
+
+// Update session state record tied to session cookie
+fn update_session<F: FnOnce(&mut Session)>(
+    conn: &BlockplotDbConn,
+    cookies: &Cookies,
+    session_db: &SessionDB,
+    change: F,
+) {
+    let session_cookie: Option<String> = cookies
+        .get("session")
+        .and_then(|cookie| cookie.value().parse().ok());
+    if let Some(session_id) = session_cookie {
+        let store = session_db.0.store(conn);
+        match store.get(&session_id) {
+            Ok(Some(mut session)) => {
+                change(&mut session);
+                if let Err(error) = store.insert(&session_id, session) {
+                    println!("Error updating session record in backend: {}", error);
+                }
+            }
+            Ok(None) => {}
+            Err(error) => println!("Error reading session record in backend: {}", error),
+        }
+    }
+}
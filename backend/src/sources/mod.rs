@@ -0,0 +1,106 @@
+use crate::db::models::{Skillblock, User};
+
+use anyhow::{anyhow, Error};
+
+use chrono::NaiveDate;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub mod rescuetime;
+pub mod toggl;
+pub mod wakatime;
+
+pub use rescuetime::RescueTime;
+pub use toggl::Toggl;
+pub use wakatime::WakaTime;
+
+// Implemented by every external service capable of supplying time data
+// for a skillblock. Returned map holds the total number of seconds spent
+// on a category for each day within the requested date range
+pub trait TimeSource {
+    fn fetch_daily_totals(
+        &self,
+        category: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<NaiveDate, i32>, Error>;
+
+    // Earliest date the service still serves data for, given today's date.
+    // Sources without a history limit return None
+    fn earliest_date(&self, _today: NaiveDate) -> Option<NaiveDate> {
+        None
+    }
+}
+
+// Enumeration of supported time sources. Stored on each skillblock record
+// as a lowercase string. Manual skillblocks draw no data from an external
+// service and are filled in through the entries api instead
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeSourceKind {
+    RescueTime,
+    Toggl,
+    WakaTime,
+    Manual,
+}
+
+impl TimeSourceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeSourceKind::RescueTime => "rescuetime",
+            TimeSourceKind::Toggl => "toggl",
+            TimeSourceKind::WakaTime => "wakatime",
+            TimeSourceKind::Manual => "manual",
+        }
+    }
+}
+
+impl FromStr for TimeSourceKind {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "rescuetime" => Ok(TimeSourceKind::RescueTime),
+            "toggl" => Ok(TimeSourceKind::Toggl),
+            "wakatime" => Ok(TimeSourceKind::WakaTime),
+            "manual" => Ok(TimeSourceKind::Manual),
+            _ => Err(anyhow!("Unknown time source: {}", value)),
+        }
+    }
+}
+
+// Build time source a skillblock draws its data from, using credentials
+// stored on the owning user record
+pub fn source_for(user: &User, skillblock: &Skillblock) -> Result<Box<dyn TimeSource>, Error> {
+    match skillblock.source.parse::<TimeSourceKind>()? {
+        TimeSourceKind::RescueTime => {
+            let api_key = user
+                .api_key
+                .clone()
+                .ok_or_else(|| anyhow!("RescueTime api key not found"))?;
+
+            Ok(Box::new(RescueTime::new(
+                api_key,
+                skillblock.offline_category,
+            )))
+        }
+        TimeSourceKind::Toggl => {
+            let api_token = user
+                .toggl_api_key
+                .clone()
+                .ok_or_else(|| anyhow!("Toggl api token not found"))?;
+
+            Ok(Box::new(Toggl::new(api_token)))
+        }
+        TimeSourceKind::WakaTime => {
+            let api_key = user
+                .wakatime_api_key
+                .clone()
+                .ok_or_else(|| anyhow!("WakaTime api key not found"))?;
+
+            Ok(Box::new(WakaTime::new(api_key)))
+        }
+        TimeSourceKind::Manual => Err(anyhow!("Manual skillblocks have no time source")),
+    }
+}
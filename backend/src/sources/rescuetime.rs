@@ -0,0 +1,67 @@
+use super::TimeSource;
+
+use anyhow::Error;
+
+use chrono::NaiveDate;
+
+use rusty_rescuetime::analytic_data::{AnalyticData, QueryKind};
+use rusty_rescuetime::parameters::Parameters;
+use rusty_rescuetime::parameters::PerspectiveOptions::Interval;
+use rusty_rescuetime::parameters::ResolutionOptions::Day;
+use rusty_rescuetime::parameters::RestrictData::{Date, Thing};
+use rusty_rescuetime::parameters::RestrictOptions::{Category, Overview};
+
+use std::collections::HashMap;
+
+// Time source backed by the RescueTime analytic data api
+pub struct RescueTime {
+    api_key: String,
+    offline_category: bool,
+}
+
+impl RescueTime {
+    pub fn new(api_key: String, offline_category: bool) -> Self {
+        Self {
+            api_key,
+            offline_category,
+        }
+    }
+}
+
+impl TimeSource for RescueTime {
+    fn fetch_daily_totals(
+        &self,
+        category: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<NaiveDate, i32>, Error> {
+        // Offline categories are restricted by category, while
+        // online categories are restricted by overview
+        let restrict_kind = if self.offline_category {
+            Category
+        } else {
+            Overview
+        };
+
+        let query_parameters = Parameters::new(
+            Some(Interval),
+            Some(Day),
+            Some(Date(start.to_string(), end.to_string())),
+            Some(restrict_kind),
+            Some(Thing(category.to_string())),
+            None,
+        );
+
+        let payload = AnalyticData::fetch(&self.api_key, query_parameters, String::from("json"))?;
+
+        // Sum total time for given category by day
+        let mut daily_totals = HashMap::new();
+        for query in payload.rows {
+            if let QueryKind::SizeSixString(value) = query {
+                *daily_totals.entry(value.perspective.date()).or_insert(0) += value.time_spent;
+            }
+        }
+
+        Ok(daily_totals)
+    }
+}
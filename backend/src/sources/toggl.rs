@@ -0,0 +1,186 @@
+use super::TimeSource;
+
+use anyhow::{anyhow, Error};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use std::collections::HashMap;
+
+const TOGGL_API_URL: &str = "https://api.track.toggl.com/api/v9";
+
+// Number of days back /me/time_entries serves entries for. Older
+// entries are only available through Toggl's reports api
+const HISTORY_DAYS: i64 = 90;
+
+// Time source backed by the Toggl Track api. A skillblock category is
+// mapped to either a Toggl project or tag, written as "project:<name>"
+// or "tag:<name>". Categories without a prefix are treated as project names
+pub struct Toggl {
+    api_token: String,
+    base_url: String,
+}
+
+// User record returned from /me endpoint
+#[derive(Debug, Deserialize)]
+struct TogglMe {
+    timezone: Option<String>,
+}
+
+// Project record returned from /me/projects endpoint
+#[derive(Debug, Deserialize)]
+struct TogglProject {
+    id: i64,
+    name: String,
+}
+
+// Time entry record returned from /me/time_entries endpoint
+#[derive(Debug, Deserialize)]
+struct TogglTimeEntry {
+    start: String,
+    duration: i64,
+    project_id: Option<i64>,
+    tags: Option<Vec<String>>,
+}
+
+// Project or tag a skillblock category refers to
+enum TogglTarget {
+    Project(String),
+    Tag(String),
+}
+
+impl TogglTarget {
+    fn parse(category: &str) -> Self {
+        if let Some(tag) = category.strip_prefix("tag:") {
+            TogglTarget::Tag(tag.trim().to_string())
+        } else {
+            let project = category.strip_prefix("project:").unwrap_or(category);
+            TogglTarget::Project(project.trim().to_string())
+        }
+    }
+}
+
+impl Toggl {
+    pub fn new(api_token: String) -> Self {
+        Self::with_base_url(api_token, TOGGL_API_URL.to_string())
+    }
+
+    // Point client at a different api host. Used to test
+    // against a local fake server
+    pub fn with_base_url(api_token: String, base_url: String) -> Self {
+        Self {
+            api_token,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // Toggl authenticates api tokens through basic auth, using
+    // "api_token" as the password
+    fn get(&self, path: &str) -> reqwest::blocking::RequestBuilder {
+        let client = reqwest::blocking::Client::new();
+        client
+            .get(&format!("{}{}", self.base_url, path))
+            .basic_auth(&self.api_token, Some("api_token"))
+    }
+
+    // Timezone set on the user's Toggl profile. Falls back to UTC
+    // when none is set or it isn't a known zone name
+    fn user_timezone(&self) -> Result<Tz, Error> {
+        let me: TogglMe = self.get("/me").send()?.error_for_status()?.json()?;
+
+        let timezone = me
+            .timezone
+            .and_then(|timezone| timezone.parse::<Tz>().ok())
+            .unwrap_or(Tz::UTC);
+
+        Ok(timezone)
+    }
+
+    fn find_project_id(&self, name: &str) -> Result<i64, Error> {
+        let projects: Vec<TogglProject> = self
+            .get("/me/projects")
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        projects
+            .into_iter()
+            .find(|project| project.name.eq_ignore_ascii_case(name))
+            .map(|project| project.id)
+            .ok_or_else(|| anyhow!("Toggl project not found: {}", name))
+    }
+}
+
+impl TimeSource for Toggl {
+    fn fetch_daily_totals(
+        &self,
+        category: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<NaiveDate, i32>, Error> {
+        let target = TogglTarget::parse(category);
+        let project_id = match &target {
+            TogglTarget::Project(name) => Some(self.find_project_id(name)?),
+            TogglTarget::Tag(_) => None,
+        };
+
+        let timezone = self.user_timezone()?;
+
+        // Entries come back with UTC timestamps, so widen query by a day on
+        // either side to catch entries that fall on a requested day in the
+        // user's timezone. Toggl treats end_date as exclusive and refuses
+        // start dates past its history limit
+        let today = Utc::now().with_timezone(&timezone).naive_local().date();
+        let query_start = std::cmp::max(
+            start - Duration::days(1),
+            self.earliest_date(today).unwrap_or(start),
+        );
+        let query_end = end + Duration::days(2);
+        let entries: Vec<TogglTimeEntry> = self
+            .get("/me/time_entries")
+            .query(&[
+                ("start_date", query_start.to_string()),
+                ("end_date", query_end.to_string()),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        // Sum durations of matching entries by day. Running entries
+        // report a negative duration and are skipped
+        let mut daily_totals = HashMap::new();
+        for entry in entries {
+            if entry.duration < 0 {
+                continue;
+            }
+
+            let matches_target = match &target {
+                TogglTarget::Project(_) => entry.project_id == project_id,
+                TogglTarget::Tag(tag) => entry.tags.as_ref().map_or(false, |tags| {
+                    tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+                }),
+            };
+            if !matches_target {
+                continue;
+            }
+
+            // Entries count towards the day they started on in the
+            // user's Toggl timezone, not the day in UTC
+            let day = DateTime::parse_from_rfc3339(&entry.start)?
+                .with_timezone(&timezone)
+                .naive_local()
+                .date();
+            if day < start || day > end {
+                continue;
+            }
+
+            *daily_totals.entry(day).or_insert(0) += entry.duration as i32;
+        }
+
+        Ok(daily_totals)
+    }
+
+    fn earliest_date(&self, today: NaiveDate) -> Option<NaiveDate> {
+        Some(today - Duration::days(HISTORY_DAYS))
+    }
+}
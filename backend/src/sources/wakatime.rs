@@ -0,0 +1,122 @@
+use super::TimeSource;
+
+use anyhow::Error;
+
+use chrono::NaiveDate;
+
+use std::collections::HashMap;
+
+const WAKATIME_API_URL: &str = "https://wakatime.com/api/v1";
+
+// Time source backed by the WakaTime summaries api. A skillblock category
+// is mapped to either a WakaTime project or language, written as
+// "project:<name>" or "language:<name>". Categories without a prefix are
+// treated as project names
+pub struct WakaTime {
+    api_key: String,
+    base_url: String,
+}
+
+// Payload returned from /users/current/summaries endpoint
+#[derive(Debug, Deserialize)]
+struct WakaTimeSummaries {
+    data: Vec<WakaTimeSummary>,
+}
+
+// Coding activity summary for a single day
+#[derive(Debug, Deserialize)]
+struct WakaTimeSummary {
+    range: WakaTimeRange,
+    #[serde(default)]
+    languages: Vec<WakaTimeItem>,
+    #[serde(default)]
+    projects: Vec<WakaTimeItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WakaTimeRange {
+    date: NaiveDate,
+}
+
+// Time spent on a single project or language
+#[derive(Debug, Deserialize)]
+struct WakaTimeItem {
+    name: String,
+    total_seconds: f64,
+}
+
+// Project or language a skillblock category refers to
+enum WakaTimeTarget {
+    Project(String),
+    Language(String),
+}
+
+impl WakaTimeTarget {
+    fn parse(category: &str) -> Self {
+        if let Some(language) = category.strip_prefix("language:") {
+            WakaTimeTarget::Language(language.trim().to_string())
+        } else {
+            let project = category.strip_prefix("project:").unwrap_or(category);
+            WakaTimeTarget::Project(project.trim().to_string())
+        }
+    }
+}
+
+impl WakaTime {
+    pub fn new(api_key: String) -> Self {
+        Self::with_base_url(api_key, WAKATIME_API_URL.to_string())
+    }
+
+    // Point client at a different api host. Used to test
+    // against a local fake server
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl TimeSource for WakaTime {
+    fn fetch_daily_totals(
+        &self,
+        category: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<HashMap<NaiveDate, i32>, Error> {
+        let target = WakaTimeTarget::parse(category);
+
+        let client = reqwest::blocking::Client::new();
+        let summaries: WakaTimeSummaries = client
+            .get(&format!("{}/users/current/summaries", self.base_url))
+            .query(&[
+                ("start", start.to_string()),
+                ("end", end.to_string()),
+                ("api_key", self.api_key.clone()),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        // Pick out coding seconds of targeted project or language for each day
+        let mut daily_totals = HashMap::new();
+        for summary in summaries.data {
+            let (items, name) = match &target {
+                WakaTimeTarget::Project(name) => (&summary.projects, name),
+                WakaTimeTarget::Language(name) => (&summary.languages, name),
+            };
+
+            let seconds: f64 = items
+                .iter()
+                .filter(|item| item.name.eq_ignore_ascii_case(name))
+                .map(|item| item.total_seconds)
+                .sum();
+
+            if seconds > 0.0 {
+                daily_totals.insert(summary.range.date, seconds.round() as i32);
+            }
+        }
+
+        Ok(daily_totals)
+    }
+}
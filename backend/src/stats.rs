@@ -0,0 +1,283 @@
+use chrono::{Datelike, Duration, NaiveDate};
+
+use std::collections::BTreeMap;
+
+// Run of consecutive days having time data. Dates are
+// left empty when no day has any time data
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Streak {
+    pub days: i32,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+// Total seconds of a week or month, keyed by its first day
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PeriodTotal {
+    pub start: NaiveDate,
+    pub seconds: i32,
+}
+
+// Statistics served for a single skillblock. Times are in seconds,
+// means and medians only count days having time data
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct SkillblockStats {
+    pub current_streak: Streak,
+    pub longest_streak: Streak,
+    pub active_days: i32,
+    pub total_seconds: i32,
+    pub daily_max: i32,
+    pub daily_mean: i32,
+    pub daily_median: i32,
+    pub weekly_totals: Vec<PeriodTotal>,
+    pub monthly_totals: Vec<PeriodTotal>,
+}
+
+// Calculate statistics of daily time totals. Current streak
+// counts back from today, or from yesterday while today has no time yet
+pub fn skillblock_stats(date_times: &[(NaiveDate, i32)], today: NaiveDate) -> SkillblockStats {
+    // Sum any repeated days and drop days without time
+    let mut days: BTreeMap<NaiveDate, i32> = BTreeMap::new();
+    for (day_date, day_time) in date_times {
+        *days.entry(*day_date).or_insert(0) += day_time;
+    }
+    days.retain(|_, day_time| *day_time > 0);
+
+    let times: Vec<i32> = days.values().cloned().collect();
+    let total_seconds = times.iter().sum();
+
+    SkillblockStats {
+        current_streak: current_streak(&days, today),
+        longest_streak: longest_streak(&days),
+        active_days: times.len() as i32,
+        total_seconds,
+        daily_max: times.iter().cloned().max().unwrap_or(0),
+        daily_mean: mean(&times),
+        daily_median: median(times),
+        weekly_totals: period_totals(&days, week_start),
+        monthly_totals: period_totals(&days, |day| day.with_day(1).unwrap()),
+    }
+}
+
+fn current_streak(days: &BTreeMap<NaiveDate, i32>, today: NaiveDate) -> Streak {
+    let end = if days.contains_key(&today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+
+    let mut start = end;
+    while days.contains_key(&start) {
+        start -= Duration::days(1);
+    }
+
+    let length = (end - start).num_days() as i32;
+    if length == 0 {
+        return Streak {
+            days: 0,
+            start: None,
+            end: None,
+        };
+    }
+
+    Streak {
+        days: length,
+        start: Some(start + Duration::days(1)),
+        end: Some(end),
+    }
+}
+
+// Longest run of consecutive days. Earliest run wins a tie
+fn longest_streak(days: &BTreeMap<NaiveDate, i32>) -> Streak {
+    let mut longest = Streak {
+        days: 0,
+        start: None,
+        end: None,
+    };
+    let mut run: Option<(NaiveDate, NaiveDate)> = None;
+
+    // Runs are compared as they end, including one ending on the last day
+    for day in days.keys().cloned() {
+        run = match run {
+            Some((start, end)) if end + Duration::days(1) == day => Some((start, day)),
+            _ => Some((day, day)),
+        };
+
+        let (start, end) = run.unwrap();
+        let length = (end - start).num_days() as i32 + 1;
+        if length > longest.days {
+            longest = Streak {
+                days: length,
+                start: Some(start),
+                end: Some(end),
+            };
+        }
+    }
+
+    longest
+}
+
+fn mean(times: &[i32]) -> i32 {
+    if times.is_empty() {
+        return 0;
+    }
+
+    let total: i64 = times.iter().map(|time| *time as i64).sum();
+    (total as f64 / times.len() as f64).round() as i32
+}
+
+fn median(mut times: Vec<i32>) -> i32 {
+    if times.is_empty() {
+        return 0;
+    }
+
+    times.sort();
+    let middle = times.len() / 2;
+    if middle * 2 == times.len() {
+        ((times[middle - 1] as f64 + times[middle] as f64) / 2.0).round() as i32
+    } else {
+        times[middle]
+    }
+}
+
+// Weeks start on sunday, matching columns of the block grid
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_sunday() as i64)
+}
+
+// Sum daily times into periods, keyed by first day of period
+fn period_totals<F>(days: &BTreeMap<NaiveDate, i32>, period_start: F) -> Vec<PeriodTotal>
+where
+    F: Fn(NaiveDate) -> NaiveDate,
+{
+    let mut totals: BTreeMap<NaiveDate, i32> = BTreeMap::new();
+    for (day, day_time) in days {
+        *totals.entry(period_start(*day)).or_insert(0) += day_time;
+    }
+
+    totals
+        .into_iter()
+        .map(|(start, seconds)| PeriodTotal { start, seconds })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    // Fixture of date_times rows: a three day run in january, a gap,
+    // then a four day run ending on the last recorded day
+    fn fixture() -> Vec<(NaiveDate, i32)> {
+        vec![
+            (date(2021, 1, 30), 600),
+            (date(2021, 1, 31), 1200),
+            (date(2021, 2, 1), 1800),
+            (date(2021, 2, 2), 0),
+            (date(2021, 2, 5), 3600),
+            (date(2021, 2, 6), 300),
+            (date(2021, 2, 7), 900),
+            (date(2021, 2, 8), 2400),
+        ]
+    }
+
+    #[test]
+    fn longest_streak_counts_run_through_last_day() {
+        let stats = skillblock_stats(&fixture(), date(2021, 3, 1));
+
+        assert_eq!(
+            stats.longest_streak,
+            Streak {
+                days: 4,
+                start: Some(date(2021, 2, 5)),
+                end: Some(date(2021, 2, 8)),
+            }
+        );
+    }
+
+    #[test]
+    fn current_streak_counts_back_from_today_or_yesterday() {
+        let from_today = skillblock_stats(&fixture(), date(2021, 2, 8));
+        let from_yesterday = skillblock_stats(&fixture(), date(2021, 2, 9));
+        let broken = skillblock_stats(&fixture(), date(2021, 2, 10));
+
+        assert_eq!(from_today.current_streak.days, 4);
+        assert_eq!(from_yesterday.current_streak, from_today.current_streak);
+        assert_eq!(from_today.current_streak.start, Some(date(2021, 2, 5)));
+        assert_eq!(broken.current_streak.days, 0);
+        assert_eq!(broken.current_streak.start, None);
+    }
+
+    #[test]
+    fn mean_and_median_only_count_active_days() {
+        let stats = skillblock_stats(&fixture(), date(2021, 3, 1));
+
+        assert_eq!(stats.active_days, 7);
+        assert_eq!(stats.total_seconds, 10800);
+        assert_eq!(stats.daily_max, 3600);
+        // 10800 seconds over 7 days, rounded
+        assert_eq!(stats.daily_mean, 1543);
+        assert_eq!(stats.daily_median, 1200);
+    }
+
+    #[test]
+    fn median_of_even_count_averages_middle_days() {
+        let stats = skillblock_stats(
+            &[(date(2021, 5, 1), 100), (date(2021, 5, 3), 301)],
+            date(2021, 5, 3),
+        );
+
+        assert_eq!(stats.daily_median, 201);
+    }
+
+    #[test]
+    fn totals_are_grouped_by_sunday_week_and_calendar_month() {
+        let stats = skillblock_stats(&fixture(), date(2021, 3, 1));
+
+        // January 30th 2021 was a saturday
+        assert_eq!(
+            stats.weekly_totals,
+            vec![
+                PeriodTotal {
+                    start: date(2021, 1, 24),
+                    seconds: 600,
+                },
+                PeriodTotal {
+                    start: date(2021, 1, 31),
+                    seconds: 6900,
+                },
+                PeriodTotal {
+                    start: date(2021, 2, 7),
+                    seconds: 3300,
+                },
+            ]
+        );
+        assert_eq!(
+            stats.monthly_totals,
+            vec![
+                PeriodTotal {
+                    start: date(2021, 1, 1),
+                    seconds: 1800,
+                },
+                PeriodTotal {
+                    start: date(2021, 2, 1),
+                    seconds: 9000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_time_data_gives_zeroed_stats() {
+        let stats = skillblock_stats(&[], date(2021, 3, 1));
+
+        assert_eq!(stats.active_days, 0);
+        assert_eq!(stats.daily_mean, 0);
+        assert_eq!(stats.daily_median, 0);
+        assert_eq!(stats.longest_streak.days, 0);
+        assert!(stats.weekly_totals.is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::sources::TimeSource;
+
+use anyhow::Error;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use std::collections::HashMap;
+use std::thread;
+
+// Number of times a chunk is requested before it's given up on
+pub const CHUNK_ATTEMPTS: u32 = 3;
+
+// Delay before the first retry of a chunk, doubled on every retry after
+pub const RETRY_DELAY_MS: i64 = 500;
+
+// Date one year before given date. Leap days fall back to February 28th
+pub fn year_before(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year() - 1, date.month(), date.day())
+        .unwrap_or_else(|| NaiveDate::from_ymd(date.year() - 1, date.month(), date.day() - 1))
+}
+
+// Split date range into chunks that each cover at most one calendar month.
+// First and last chunks are cut short to fit the range
+pub fn month_chunks(start: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+
+    while chunk_start <= end {
+        let next_month = if chunk_start.month() == 12 {
+            NaiveDate::from_ymd(chunk_start.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd(chunk_start.year(), chunk_start.month() + 1, 1)
+        };
+        let chunk_end = std::cmp::min(next_month.pred(), end);
+
+        chunks.push((chunk_start, chunk_end));
+        chunk_start = chunk_end.succ();
+    }
+
+    chunks
+}
+
+// Drop chunks already covered in full by a completed range
+pub fn pending_chunks(
+    chunks: Vec<(NaiveDate, NaiveDate)>,
+    completed: &[(NaiveDate, NaiveDate)],
+) -> Vec<(NaiveDate, NaiveDate)> {
+    chunks
+        .into_iter()
+        .filter(|(chunk_start, chunk_end)| {
+            !completed.iter().any(|(range_start, range_end)| {
+                range_start <= chunk_start && range_end >= chunk_end
+            })
+        })
+        .collect()
+}
+
+// Request a single chunk of time data from time source, retrying with
+// an exponential backoff. Error of the last attempt is returned if
+// every attempt fails
+pub fn fetch_chunk(
+    source: &dyn TimeSource,
+    category: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    attempts: u32,
+    retry_delay: Duration,
+) -> Result<HashMap<NaiveDate, i32>, Error> {
+    let mut delay = retry_delay;
+    let mut attempt = 1;
+
+    loop {
+        match source.fetch_daily_totals(category, start, end) {
+            Ok(totals) => return Ok(totals),
+            Err(error) if attempt >= attempts => return Err(error),
+            Err(error) => {
+                println!(
+                    "Attempt {} fetching {} to {} failed, retrying: {}",
+                    attempt, start, end, error
+                );
+                thread::sleep(delay.to_std().unwrap_or_default());
+                delay = delay * 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::anyhow;
+
+    use std::cell::Cell;
+
+    // Time source failing a set number of times before answering
+    struct FlakySource {
+        failures: u32,
+        calls: Cell<u32>,
+    }
+
+    impl TimeSource for FlakySource {
+        fn fetch_daily_totals(
+            &self,
+            _category: &str,
+            start: NaiveDate,
+            _end: NaiveDate,
+        ) -> Result<HashMap<NaiveDate, i32>, Error> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() <= self.failures {
+                return Err(anyhow!("Service unavailable"));
+            }
+
+            let mut totals = HashMap::new();
+            totals.insert(start, 60);
+            Ok(totals)
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn month_chunks_cover_range_by_calendar_month() {
+        let chunks = month_chunks(date(2020, 11, 18), date(2021, 2, 3));
+
+        assert_eq!(
+            chunks,
+            vec![
+                (date(2020, 11, 18), date(2020, 11, 30)),
+                (date(2020, 12, 1), date(2020, 12, 31)),
+                (date(2021, 1, 1), date(2021, 1, 31)),
+                (date(2021, 2, 1), date(2021, 2, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn year_before_handles_leap_day() {
+        assert_eq!(year_before(date(2021, 8, 1)), date(2020, 8, 1));
+        assert_eq!(year_before(date(2020, 2, 29)), date(2019, 2, 28));
+    }
+
+    #[test]
+    fn pending_chunks_skips_completed_ranges() {
+        let chunks = month_chunks(date(2021, 1, 10), date(2021, 3, 31));
+        let completed = vec![(date(2021, 1, 1), date(2021, 1, 31))];
+
+        assert_eq!(
+            pending_chunks(chunks, &completed),
+            vec![
+                (date(2021, 2, 1), date(2021, 2, 28)),
+                (date(2021, 3, 1), date(2021, 3, 31)),
+            ]
+        );
+    }
+
+    #[test]
+    fn fetch_chunk_retries_until_success() {
+        let source = FlakySource {
+            failures: 2,
+            calls: Cell::new(0),
+        };
+
+        let totals = fetch_chunk(
+            &source,
+            "Software Development",
+            date(2021, 1, 1),
+            date(2021, 1, 31),
+            3,
+            Duration::zero(),
+        )
+        .unwrap();
+
+        assert_eq!(source.calls.get(), 3);
+        assert_eq!(totals.get(&date(2021, 1, 1)), Some(&60));
+    }
+
+    #[test]
+    fn fetch_chunk_gives_up_after_last_attempt() {
+        let source = FlakySource {
+            failures: 5,
+            calls: Cell::new(0),
+        };
+
+        let result = fetch_chunk(
+            &source,
+            "Software Development",
+            date(2021, 1, 1),
+            date(2021, 1, 31),
+            3,
+            Duration::zero(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(source.calls.get(), 3);
+    }
+}
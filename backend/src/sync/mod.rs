@@ -0,0 +1,146 @@
+use crate::db::models::{NewDateTime, NewSyncRange, Skillblock};
+use crate::db::operations::{add_sync_range, query_sync_ranges, upsert_date_times};
+use crate::sources::TimeSource;
+
+use chrono::{Duration, Local, NaiveDate};
+
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use diesel::Connection;
+
+use std::collections::HashMap;
+
+pub mod backfill;
+pub mod worker;
+
+pub use worker::{spawn_sync_worker, sync_skillblock, sync_user, SyncHandle};
+
+use backfill::{fetch_chunk, month_chunks, pending_chunks, CHUNK_ATTEMPTS, RETRY_DELAY_MS};
+
+// Sync state of a skillblock, stored on its record as a lowercase string.
+// Partial syncs stored some chunks of time data, but not all of them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncStatus {
+    Pending,
+    Synced,
+    Partial,
+    Failed,
+    Manual,
+}
+
+impl SyncStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncStatus::Pending => "pending",
+            SyncStatus::Synced => "synced",
+            SyncStatus::Partial => "partial",
+            SyncStatus::Failed => "failed",
+            SyncStatus::Manual => "manual",
+        }
+    }
+}
+
+// Outcome of a backfill run. Failed chunks are left unrecorded
+// so they get requested again on the next run
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+    pub fetched: usize,
+    pub skipped: usize,
+    pub failed: Vec<(NaiveDate, NaiveDate)>,
+}
+
+// Pull time data of a skillblock for every month sized chunk of the
+// date range that hasn't been fully fetched yet. Chunks ending before
+// yesterday are recorded as complete once stored, since time sources
+// may still be receiving data for more recent days. Range is cut short to
+// the history a time source still serves, so chunks it refuses don't fail
+// on every run
+pub fn backfill_skillblock(
+    conn: &PgConnection,
+    source: &dyn TimeSource,
+    skillblock: &Skillblock,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<BackfillReport, Error> {
+    let completed: Vec<(NaiveDate, NaiveDate)> = query_sync_ranges(conn, skillblock)?
+        .into_iter()
+        .map(|sync_range| (sync_range.range_start, sync_range.range_end))
+        .collect();
+
+    let today = Local::now().date().naive_utc();
+    let start = match source.earliest_date(today) {
+        Some(earliest) if earliest > start => earliest,
+        _ => start,
+    };
+
+    let chunks = month_chunks(start, end);
+    let total = chunks.len();
+    let pending = pending_chunks(chunks, &completed);
+
+    let mut report = BackfillReport {
+        skipped: total - pending.len(),
+        ..Default::default()
+    };
+    let yesterday = today.pred();
+
+    for (chunk_start, chunk_end) in pending {
+        let totals = match fetch_chunk(
+            source,
+            &skillblock.category,
+            chunk_start,
+            chunk_end,
+            CHUNK_ATTEMPTS,
+            Duration::milliseconds(RETRY_DELAY_MS),
+        ) {
+            Ok(totals) => totals,
+            Err(error) => {
+                println!(
+                    "Error fetching {} to {} for skillblock {}: {}",
+                    chunk_start, chunk_end, skillblock.block_id, error
+                );
+                report.failed.push((chunk_start, chunk_end));
+                continue;
+            }
+        };
+
+        conn.transaction::<_, Error, _>(|| {
+            store_daily_totals(conn, skillblock.block_id, &totals)?;
+
+            if chunk_end < yesterday {
+                add_sync_range(
+                    conn,
+                    NewSyncRange {
+                        block_id: skillblock.block_id,
+                        range_start: chunk_start,
+                        range_end: chunk_end,
+                        completed_at: Local::now().naive_utc(),
+                    },
+                )?;
+            }
+
+            Ok(())
+        })?;
+
+        report.fetched += 1;
+    }
+
+    Ok(report)
+}
+
+// Store daily totals of a skillblock, overwriting days already on record
+fn store_daily_totals(
+    conn: &PgConnection,
+    block_id: i32,
+    totals: &HashMap<NaiveDate, i32>,
+) -> Result<usize, Error> {
+    let date_times: Vec<NewDateTime> = totals
+        .iter()
+        .map(|(day, seconds)| NewDateTime {
+            block_id: Some(block_id),
+            day_time: *seconds,
+            day_date: day.and_hms(0, 0, 0),
+        })
+        .collect();
+
+    upsert_date_times(conn, &date_times)
+}
@@ -0,0 +1,243 @@
+use crate::db::models::{Skillblock, User};
+use crate::db::operations::{
+    query_skillblocks, query_skillblocks_due_for_sync, query_user, update_skillblock_sync,
+};
+use crate::sources::{source_for, TimeSourceKind};
+use crate::sync::backfill::year_before;
+use crate::sync::{backfill_skillblock, SyncStatus};
+
+use chrono::{Duration, Local};
+
+use diesel::pg::PgConnection;
+use diesel::Connection;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+// Longest the worker sleeps between checks for skillblocks due a sync
+const POLL_MINUTES: i64 = 5;
+
+// Minutes a skillblock waits after its first failed sync
+const RETRY_BASE_MINUTES: i64 = 15;
+
+// Longest a skillblock waits between failed syncs
+const MAX_RETRY_HOURS: i64 = 24;
+
+// Handle to background sync worker, managed by rocket. Sending a user's
+// auth id asks the worker to sync that user's skillblocks right away.
+// Disabled handles, used when no worker runs, drop every request
+pub struct SyncHandle(Option<Mutex<Sender<String>>>);
+
+impl SyncHandle {
+    pub fn disabled() -> Self {
+        SyncHandle(None)
+    }
+
+    pub fn request_sync(&self, auth_id: String) {
+        let sender = match &self.0 {
+            Some(sender) => sender,
+            None => return,
+        };
+        match sender.lock() {
+            Ok(sender) => {
+                if let Err(error) = sender.send(auth_id) {
+                    println!("Error requesting skillblock sync: {}", error);
+                }
+            }
+            Err(_) => println!("Error requesting skillblock sync: worker handle poisoned"),
+        }
+    }
+}
+
+// Spawn worker thread that keeps date_times up to date for every skillblock
+// backed by an external time source. Skillblocks are due for a sync once
+// their last_synced_at cursor is older than the sync interval. Periodic
+// passes run on their own schedule, so a steady stream of requested
+// syncs can't hold them off
+pub fn spawn_sync_worker(database_url: String, interval: Duration) -> SyncHandle {
+    let (sender, receiver) = channel::<String>();
+    let wait = std::cmp::min(interval, Duration::minutes(POLL_MINUTES))
+        .to_std()
+        .unwrap_or_default();
+
+    thread::spawn(move || {
+        let mut next_pass = Instant::now() + wait;
+        loop {
+            let timeout = next_pass.saturating_duration_since(Instant::now());
+            let requested = match receiver.recv_timeout(timeout) {
+                Ok(auth_id) => Some(auth_id),
+                Err(RecvTimeoutError::Timeout) => {
+                    next_pass = Instant::now() + wait;
+                    None
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // A panic while syncing is logged and the worker carries
+            // on, rather than stopping syncs until the next restart
+            let pass = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_pass(&database_url, requested, interval)
+            }));
+            if pass.is_err() {
+                println!("Sync worker pass panicked, continuing with next pass");
+            }
+        }
+    });
+
+    SyncHandle(Some(Mutex::new(sender)))
+}
+
+// Sync skillblocks of requested user, or every skillblock due a sync
+fn run_pass(database_url: &str, requested: Option<String>, interval: Duration) {
+    let conn = match PgConnection::establish(database_url) {
+        Ok(conn) => conn,
+        Err(error) => {
+            println!("Sync worker failed to connect to database: {}", error);
+            return;
+        }
+    };
+
+    match requested {
+        Some(auth_id) => match query_user(&conn, auth_id) {
+            Ok(Some(user)) => sync_user(&conn, &user),
+            Ok(None) => println!("Sync worker couldn't find requested user"),
+            Err(error) => println!("Error fetching user for sync: {}", error),
+        },
+        None => {
+            let now = Local::now().naive_utc();
+            match query_skillblocks_due_for_sync(&conn, now - interval, now) {
+                Ok(due) => {
+                    for (skillblock, user) in due {
+                        match user {
+                            Ok(user) => sync_skillblock(&conn, &user, &skillblock),
+                            // Counts toward backoff of skillblock, so
+                            // unreadable rows aren't retried every pass
+                            Err(error) => {
+                                println!(
+                                    "Error opening credentials for skillblock {}: {}",
+                                    skillblock.block_id, error
+                                );
+                                record_sync(&conn, &skillblock, SyncStatus::Failed);
+                            }
+                        }
+                    }
+                }
+                Err(error) => println!("Error fetching skillblocks due for sync: {}", error),
+            }
+        }
+    }
+}
+
+// Refresh time data of every skillblock a user owns, skipping
+// failed skillblocks still waiting out their backoff
+pub fn sync_user(conn: &PgConnection, user: &User) {
+    let skillblocks = match query_skillblocks(conn, user) {
+        Ok(skillblocks) => skillblocks,
+        Err(error) => {
+            println!("Error fetching skillblocks for sync: {}", error);
+            return;
+        }
+    };
+
+    // Requested syncs honour backoff of failed skillblocks, so
+    // repeated requests can't hammer a time source that's failing
+    let now = Local::now().naive_utc();
+    for skillblock in skillblocks {
+        match skillblock.next_attempt_at {
+            Some(next_attempt) if next_attempt > now => continue,
+            _ => sync_skillblock(conn, user, &skillblock),
+        }
+    }
+}
+
+// Refresh time data of a single skillblock and record the outcome on its
+// record. Cursor only moves forward once time data has been stored, so
+// failed skillblocks are picked up again on the worker's next pass
+pub fn sync_skillblock(conn: &PgConnection, user: &User, skillblock: &Skillblock) {
+    // Manual skillblocks have no time source to sync with
+    if skillblock.source == TimeSourceKind::Manual.as_str() {
+        return;
+    }
+
+    let current_date = Local::now().date().naive_utc();
+    let status = match source_for(user, skillblock) {
+        Ok(source) => match backfill_skillblock(
+            conn,
+            source.as_ref(),
+            skillblock,
+            year_before(current_date),
+            current_date,
+        ) {
+            Ok(report) if report.failed.is_empty() => SyncStatus::Synced,
+            Ok(report) if report.fetched > 0 => {
+                println!(
+                    "Skillblock {} missing {} chunks of time data",
+                    skillblock.block_id,
+                    report.failed.len()
+                );
+                SyncStatus::Partial
+            }
+            Ok(_) => SyncStatus::Failed,
+            Err(error) => {
+                println!("Error saving date data to db: {}", error);
+                SyncStatus::Failed
+            }
+        },
+        Err(error) => {
+            println!("Error building time source: {}", error);
+            SyncStatus::Failed
+        }
+    };
+
+    record_sync(conn, skillblock, status);
+}
+
+// Record outcome of a sync on skillblock record. Failed skillblocks
+// back off before being tried again, so a bad api key doesn't hammer
+// its time source on every pass
+fn record_sync(conn: &PgConnection, skillblock: &Skillblock, status: SyncStatus) {
+    let now = Local::now().naive_utc();
+    let (synced_at, failures, next_attempt) = match status {
+        SyncStatus::Failed => {
+            let failures = skillblock.sync_failures + 1;
+            (None, failures, Some(now + retry_delay(failures)))
+        }
+        _ => (Some(now), 0, None),
+    };
+    if let Err(error) = update_skillblock_sync(
+        conn,
+        skillblock.block_id,
+        status.as_str(),
+        synced_at,
+        failures,
+        next_attempt,
+    ) {
+        println!("Error updating skillblock sync status: {}", error);
+    }
+}
+
+// Time to wait before syncing a skillblock again after a number of
+// failed syncs in a row. Doubles with every failure, up to a day
+fn retry_delay(failures: i32) -> Duration {
+    let doublings = std::cmp::min(failures.max(1) - 1, 10) as u32;
+    std::cmp::min(
+        Duration::minutes(RETRY_BASE_MINUTES * 2_i64.pow(doublings)),
+        Duration::hours(MAX_RETRY_HOURS),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_day() {
+        assert_eq!(retry_delay(1), Duration::minutes(RETRY_BASE_MINUTES));
+        assert_eq!(retry_delay(2), Duration::minutes(RETRY_BASE_MINUTES * 2));
+        assert_eq!(retry_delay(3), Duration::minutes(RETRY_BASE_MINUTES * 4));
+        assert_eq!(retry_delay(50), Duration::hours(MAX_RETRY_HOURS));
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+// Spawn fake third party api server on a random port. Each route pairs a
+// request line prefix with the json body served for it. Requests rejected
+// by the authorized check are answered with a 401
+pub fn spawn_fake_api(
+    routes: Vec<(&'static str, &'static str)>,
+    authorized: fn(&str) -> bool,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            // Read request headers. Requests sent by clients carry no body
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+
+            let route = routes
+                .iter()
+                .find(|(prefix, _)| request.starts_with(prefix));
+            let (status, body) = if !authorized(&request) {
+                ("401 Unauthorized", "")
+            } else if let Some((_, body)) = route {
+                ("200 OK", *body)
+            } else {
+                ("404 Not Found", "")
+            };
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    format!("http://127.0.0.1:{}", port)
+}
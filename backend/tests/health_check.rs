@@ -2,16 +2,31 @@
 extern crate diesel_migrations;
 
 use backend::auth::auth0::{AuthParameters, SessionDB};
+use backend::auth::sessions::token_hash;
 use backend::configuration::{get_configuration, DatabaseSettings};
-use backend::db::models::{TimeWrapper, User};
-use backend::db::operations::query_user;
+use backend::db::models::{
+    ApiToken, CredentialChanges, DateTime, DeletionConfirmation, FieldError, Goal, IssuedApiToken,
+    NewSkillblock, NewUser, ShareLink, Skillblock, TimeWrapper, User,
+};
+use backend::db::operations::{
+    create_skillblock, create_user, query_skillblocks, query_skillblocks_due_for_sync, query_user,
+    update_user_credentials,
+};
+use backend::db::schema::deletion_tokens;
+use backend::errors::ErrorBody;
+use backend::goals::GoalProgress;
+use backend::import::ImportReport;
 use backend::rocket;
+use backend::stats::SkillblockStats;
+use backend::sync::sync_user;
 use diesel::Connection;
+use diesel::ExpressionMethods;
 use diesel::PgConnection;
+use diesel::QueryDsl;
 use diesel::RunQueryDsl;
 use diesel_migrations::embed_migrations;
 use rocket::config::Value;
-use rocket::http::{ContentType, Status};
+use rocket::http::{ContentType, Cookie, Header, Status};
 use rocket::local::{Client, LocalResponse};
 use rocket::Rocket;
 use rocket::State;
@@ -199,6 +214,52 @@ fn create_mock_skillblock(app: &TestApp) -> LocalResponse {
     response
 }
 
+// Log in test user and create mock skillblock, returning
+// user along with id of the new skillblock
+fn logged_in_block(app: &TestApp) -> (User, i32) {
+    let config_result = configure_testuser(app).unwrap();
+    let rocket_instance = app.client.rocket();
+    create_mock_skillblock(app);
+
+    let user = retrieve_user(app, config_result, rocket_instance).unwrap();
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let block_id = query_skillblocks(&conn, &user).unwrap()[0].block_id;
+
+    (user, block_id)
+}
+
+// Create skillblock of given source through json endpoint, returning its id
+fn create_json_skillblock(app: &TestApp, body: &str) -> i32 {
+    let req = app
+        .client
+        .post("/api/v1/skillblocks")
+        .body(body.to_string())
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let skillblock: Skillblock = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    skillblock.block_id
+}
+
+fn create_manual_skillblock(app: &TestApp) -> i32 {
+    create_json_skillblock(
+        app,
+        r#"{"category": "Guitar", "description": "Guitar practice", "skill_name": "Guitar", "source": "manual"}"#,
+    )
+}
+
+// Session token set by login, kept so it can be replayed after logout
+fn session_token(response: &LocalResponse) -> String {
+    response
+        .cookies()
+        .into_iter()
+        .find(|cookie| cookie.name() == "session")
+        .unwrap()
+        .value()
+        .to_string()
+}
+
 // Return User struct from postgres database
 fn retrieve_user(app: &TestApp, response: LocalResponse, rocket_instance: &Rocket) -> Option<User> {
     let session_state: Option<State<SessionDB>> = State::from(rocket_instance);
@@ -207,14 +268,13 @@ fn retrieve_user(app: &TestApp, response: LocalResponse, rocket_instance: &Rocke
     let conn =
         PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
 
-    // Retrieve user id from in memory session database
+    // Retrieve user id from session store
     let user_id = match session_cookie {
         Some(session) => {
             let session_token = session.value();
             let session_db = session_state.unwrap();
-            let session_map = session_db.0.get(session_token).unwrap();
-            match *session_map {
-                Some(ref session) => session.user_id.to_string(),
+            match session_db.0.store(&conn).get(session_token).unwrap() {
+                Some(session) => session.user_id,
                 None => String::from("User id not found in session database"),
             }
         }
@@ -222,7 +282,7 @@ fn retrieve_user(app: &TestApp, response: LocalResponse, rocket_instance: &Rocke
     };
 
     // Retrieve user from postgres database
-    let pg_user = query_user(&conn, user_id);
+    let pg_user = query_user(&conn, user_id).unwrap();
 
     pg_user
 }
@@ -260,9 +320,11 @@ fn get_skillblocks_returns_401_if_user_guard_fails() {
     let app = spawn_app();
 
     let req = app.client.get("/api/skillblocks");
-    let response = req.dispatch();
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
 
     assert_eq!(response.status(), Status::Unauthorized);
+    assert_eq!(payload.code, "unauthorized");
 }
 
 #[test]
@@ -271,9 +333,11 @@ fn get_skillblocks_returns_404_if_key_not_found() {
     let _config_result = configure_testuser(&app);
 
     let req = app.client.get("/api/skillblocks");
-    let response = req.dispatch();
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
 
     assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "missing_api_key");
 }
 
 #[test]
@@ -303,67 +367,1338 @@ fn get_skillblocks_returns_200_and_payload() {
 }
 
 #[test]
-fn new_skillblocks_successfully_returns_303() {
+fn get_skillblock_returns_404_if_skillblock_not_found() {
     let app = spawn_app();
-    let config_result = configure_testuser(&app).unwrap();
-    let rocket_instance = app.client.rocket();
+    configure_testuser(&app).unwrap();
 
-    // Hit "/api/new_skillblock" endpoint
-    let response = create_mock_skillblock(&app);
+    let req = app.client.get("/api/skillblocks/0");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
 
-    // Retrieve user record from database
-    // Store number of newly created skillblocks associated with test user
-    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
-    let block_count = user.block_count;
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "skillblock_not_found");
+}
 
-    // Check database for correct number of newly created skillblocks
-    assert_eq!(block_count, 1);
-    assert_eq!(response.status(), Status::SeeOther);
+#[test]
+fn get_skillblock_stats_returns_200_and_stats() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .get(format!("/api/skillblocks/{}/stats", block_id));
+    let mut response = req.dispatch();
+    let stats: SkillblockStats = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(stats.longest_streak.days >= stats.current_streak.days);
 }
 
 #[test]
-fn new_skillblock_returns_401_if_user_not_logged_in() {
+fn get_skillblock_stats_returns_404_if_skillblock_not_found() {
     let app = spawn_app();
+    configure_testuser(&app).unwrap();
 
-    let req = app.client.post("/api/new_skillblock");
+    let req = app.client.get("/api/skillblocks/0/stats");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "skillblock_not_found");
+}
+
+#[test]
+fn set_goal_successfully_returns_200_and_goal_progress() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}/goal", block_id))
+        .body(r#"{"period": "weekly", "target_minutes": 300}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let goal: Goal = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(goal.period, "weekly");
+    assert_eq!(goal.target_minutes, 300);
+
+    // Goal progress covers every week of requested range
+    let req = app.client.get(format!(
+        "/api/skillblocks/{}/goal?from=2021-02-07&to=2021-02-20",
+        block_id
+    ));
+    let mut response = req.dispatch();
+    let progress: GoalProgress = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(progress.periods.len(), 2);
+    assert_eq!(progress.met + progress.missed, 2);
+}
+
+#[test]
+fn set_goal_returns_422_if_period_invalid() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}/goal", block_id))
+        .body(r#"{"period": "monthly", "target_minutes": 300}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "invalid_goal_period");
+}
+
+#[test]
+fn get_goal_returns_404_if_goal_not_set() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .get(format!("/api/skillblocks/{}/goal", block_id));
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "goal_not_found");
+}
+
+#[test]
+fn get_grid_svg_returns_200_and_svg() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .get(format!("/api/skillblocks/{}/grid.svg?year=2021", block_id));
+    let mut response = req.dispatch();
+    let svg = response.body_string().unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::SVG));
+    assert_eq!(svg.matches("<rect").count(), 365);
+}
+
+#[test]
+fn get_grid_png_returns_200_and_png_download() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .get(format!("/api/skillblocks/{}/grid.png?year=2021", block_id));
+    let mut response = req.dispatch();
+    let png = response.body_bytes().unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::PNG));
+    assert_eq!(
+        response.headers().get_one("Content-Disposition"),
+        Some("attachment; filename=\"programming-grid.png\"")
+    );
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn shared_grid_png_is_cached_until_time_data_changes() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/share", block_id));
+    let mut response = req.dispatch();
+    let share_link: ShareLink = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    let png_url = format!("/api/share/{}/grid.png", share_link.share_token);
+
+    let response = app.client.get(png_url.clone()).dispatch();
+    let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Cache-Control"),
+        Some("public, max-age=300")
+    );
+
+    // Image is left unrendered while client holds current tag
+    let req = app
+        .client
+        .get(png_url.clone())
+        .header(Header::new("If-None-Match", etag.clone()));
+    let mut response = req.dispatch();
+
+    assert_eq!(response.status(), Status::NotModified);
+    assert!(response.body_bytes().is_none());
+
+    // New time data changes the tag
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let today = chrono::Local::now().date().naive_utc().and_hms(0, 0, 0);
+    diesel::delete(
+        backend::db::schema::date_times::table
+            .filter(backend::db::schema::date_times::block_id.eq(block_id))
+            .filter(backend::db::schema::date_times::day_date.eq(today)),
+    )
+    .execute(&conn)
+    .unwrap();
+    diesel::insert_into(backend::db::schema::date_times::table)
+        .values((
+            backend::db::schema::date_times::block_id.eq(block_id),
+            backend::db::schema::date_times::day_time.eq(3601),
+            backend::db::schema::date_times::day_date.eq(today),
+        ))
+        .execute(&conn)
+        .unwrap();
+
+    let req = app
+        .client
+        .get(png_url)
+        .header(Header::new("If-None-Match", etag.clone()));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_ne!(response.headers().get_one("ETag"), Some(etag.as_str()));
+}
+
+#[test]
+fn shared_grid_svg_is_served_until_unshared() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/share", block_id));
+    let mut response = req.dispatch();
+    let share_link: ShareLink = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(share_link.share_token.len(), 32);
+
+    let response = app.client.get(share_link.svg_url.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let req = app
+        .client
+        .delete(format!("/api/skillblocks/{}/share", block_id));
+    let response = req.dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    let mut response = app.client.get(share_link.svg_url).dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "share_not_found");
+}
+
+#[test]
+fn export_data_returns_200_and_zip_archive() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    create_mock_skillblock(&app);
+
+    let req = app.client.get("/api/export");
+    let mut response = req.dispatch();
+    let archive = response.body_bytes().unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::ZIP));
+    // Zip archives open with a local file header signature
+    assert_eq!(&archive[..4], b"PK\x03\x04");
+}
+
+#[test]
+fn export_data_returns_401_if_user_not_logged_in() {
+    let app = spawn_app();
+
+    let req = app.client.get("/api/export");
     let response = req.dispatch();
 
     assert_eq!(response.status(), Status::Unauthorized);
 }
 
 #[test]
-fn health_check_returns_200() {
+fn delete_account_successfully_returns_204_and_removes_user_data() {
     let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let rocket_instance = app.client.rocket();
+    create_mock_skillblock(&app);
+    let session_token = session_token(&config_result);
+    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
 
-    let req = app.client.get("/health_check");
+    let req = app.client.post("/api/account/deletion");
+    let mut response = req.dispatch();
+    let confirmation: DeletionConfirmation =
+        serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    let req = app
+        .client
+        .delete("/api/account")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"confirmation_token": "{}"}}"#,
+            confirmation.confirmation_token
+        ));
     let response = req.dispatch();
 
-    assert_eq!(response.status(), Status::Ok)
+    // Check database for removal of user and skillblocks
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let remaining_blocks: i64 = backend::db::schema::skillblocks::table
+        .count()
+        .get_result(&conn)
+        .unwrap();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert!(query_user(&conn, user.auth_id).unwrap().is_none());
+    assert_eq!(remaining_blocks, 0);
+
+    // Session of deleted user is gone, even when its cookie is replayed
+    let req = app
+        .client
+        .get("/api/skillblocks")
+        .cookie(Cookie::new("session", session_token));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
 }
 
 #[test]
-fn process_login_successfully_stores_user_and_returns_303() {
-    // Arrange
+fn delete_account_returns_403_if_confirmation_token_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    app.client.post("/api/account/deletion").dispatch();
+
+    let req = app
+        .client
+        .delete("/api/account")
+        .header(ContentType::JSON)
+        .body(r#"{"confirmation_token": "not-the-token"}"#);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(payload.code, "invalid_confirmation_token");
+}
+
+#[test]
+fn delete_account_stores_only_hash_of_confirmation_token() {
     let app = spawn_app();
     let config_result = configure_testuser(&app).unwrap();
-    let response_status = config_result.status();
     let rocket_instance = app.client.rocket();
+    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
 
-    // Retrieve user from postgres database
-    let pg_user = retrieve_user(&app, config_result, rocket_instance);
+    let req = app.client.post("/api/account/deletion");
+    let mut response = req.dispatch();
+    let confirmation: DeletionConfirmation =
+        serde_json::from_str(&response.body_string().unwrap()).unwrap();
 
-    // Assert
-    assert_eq!(pg_user.is_some(), true);
-    assert_eq!(response_status, Status::SeeOther);
+    // Token is stored by its hash alone, alongside its expiry
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let stored_hash: String = deletion_tokens::table
+        .find(user.user_id)
+        .select(deletion_tokens::token_hash)
+        .first(&conn)
+        .unwrap();
+
+    assert_ne!(stored_hash, confirmation.confirmation_token);
+    assert_eq!(stored_hash, token_hash(&confirmation.confirmation_token));
+
+    // Expired token is refused and used up all the same
+    diesel::update(deletion_tokens::table.find(user.user_id))
+        .set(deletion_tokens::expires.eq(0))
+        .execute(&conn)
+        .unwrap();
+
+    let req = app
+        .client
+        .delete("/api/account")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"confirmation_token": "{}"}}"#,
+            confirmation.confirmation_token
+        ));
+    let response = req.dispatch();
+    let remaining_tokens: i64 = deletion_tokens::table.count().get_result(&conn).unwrap();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(remaining_tokens, 0);
 }
 
 #[test]
-fn process_logout_successfully_returns_303() {
+fn delete_account_returns_401_if_user_not_logged_in() {
     let app = spawn_app();
-    let _config_result = configure_testuser(&app);
 
-    let req = app.client.get("/logout");
+    let req = app
+        .client
+        .delete("/api/account")
+        .header(ContentType::JSON)
+        .body(r#"{"confirmation_token": "token"}"#);
     let response = req.dispatch();
 
-    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn get_skillblocks_returns_400_if_date_range_inverted() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .get("/api/skillblocks?from=2021-03-01&to=2021-02-01");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(payload.code, "invalid_range");
+}
+
+#[test]
+fn get_skillblocks_returns_400_if_date_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app.client.get("/api/skillblocks?from=garbage");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(payload.code, "invalid_date");
+}
+
+#[test]
+fn get_skillblocks_returns_400_if_year_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app.client.get("/api/skillblocks?year=abc");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(payload.code, "invalid_range");
+}
+
+#[test]
+fn shared_grid_svg_returns_400_if_year_invalid() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .get(format!("/api/skillblocks/{}/grid.svg?year=abc", block_id));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn get_skillblocks_returns_400_if_year_combined_with_range() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app.client.get("/api/skillblocks?year=2020&from=2020-03-01");
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(payload.code, "invalid_range");
+}
+
+#[test]
+fn new_skillblocks_successfully_returns_303() {
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let rocket_instance = app.client.rocket();
+
+    // Hit "/api/new_skillblock" endpoint
+    let response = create_mock_skillblock(&app);
+
+    // Retrieve user record from database
+    // Store number of newly created skillblocks associated with test user
+    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
+    let block_count = user.block_count;
+
+    // Check database for correct number of newly created skillblocks
+    assert_eq!(block_count, 1);
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn new_skillblock_returns_401_if_user_not_logged_in() {
+    let app = spawn_app();
+
+    let req = app.client.post("/api/new_skillblock");
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn create_skillblock_json_successfully_returns_201_and_location() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .post("/api/v1/skillblocks")
+        .body(r#"{"category": "Reading", "description": "Books read", "skill_name": "Reading", "source": "manual"}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let location = response.headers().get_one("Location").map(String::from);
+    let skillblock: Skillblock = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(
+        location,
+        Some(format!("/api/skillblocks/{}", skillblock.block_id))
+    );
+    assert_eq!(skillblock.source, "manual");
+
+    // Location points at a route serving the created skillblock
+    let req = app.client.get(location.unwrap());
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn create_skillblock_json_returns_422_and_field_errors() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .post("/api/v1/skillblocks")
+        .body(r#"{"category": "", "description": "Time spent coding", "skill_name": " ", "source": "toggl"}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+    let errors: Vec<FieldError> = serde_json::from_value(payload.details.unwrap()).unwrap();
+    let fields: Vec<String> = errors.into_iter().map(|error| error.field).collect();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "validation_failed");
+    assert_eq!(fields, vec!["category", "skill_name", "toggl_api_key"]);
+}
+
+#[test]
+fn edit_skillblock_successfully_returns_200_and_skillblock() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}", block_id))
+        .body(r#"{"skill_name": "Rust"}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let skillblock: Skillblock = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(skillblock.skill_name, "Rust");
+    assert_eq!(skillblock.category, "software ");
+}
+
+#[test]
+fn edit_skillblock_appearance_returns_200_and_skillblock() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}", block_id))
+        .body(r#"{"palette": "viridis", "thresholds": [5, 10, 15, 20]}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let skillblock: Skillblock = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(skillblock.palette, "viridis");
+    assert_eq!(skillblock.intensity_scale, "fixed");
+    assert_eq!(skillblock.thresholds, vec![5, 10, 15, 20]);
+}
+
+#[test]
+fn edit_skillblock_returns_422_if_thresholds_invalid() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}", block_id))
+        .body(r#"{"thresholds": [30, 15, 45, 60]}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "invalid_thresholds");
+}
+
+#[test]
+fn get_palettes_returns_200_and_palettes() {
+    let app = spawn_app();
+
+    let req = app.client.get("/api/palettes");
+    let mut response = req.dispatch();
+    let palettes: Vec<serde_json::Value> =
+        serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert!(palettes
+        .iter()
+        .any(|palette| palette["colorblind_safe"] == true));
+}
+
+#[test]
+fn delete_skillblock_successfully_returns_204_and_updates_block_count() {
+    let app = spawn_app();
+    let (user, block_id) = logged_in_block(&app);
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+
+    let req = app.client.delete(format!("/api/skillblocks/{}", block_id));
+    let response = req.dispatch();
+
+    // Check database for removal of skillblock
+    let user = query_user(&conn, user.auth_id).unwrap().unwrap();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert_eq!(user.block_count, 0);
+    assert_eq!(query_skillblocks(&conn, &user).unwrap().len(), 0);
+}
+
+// Insert user owning a single RescueTime skillblock, bypassing login
+fn insert_synced_user(conn: &PgConnection, auth_id: &str) -> (User, Skillblock) {
+    let now = chrono::Local::now().naive_utc();
+    let user = create_user(
+        conn,
+        NewUser {
+            auth_id: auth_id.to_string(),
+            api_key: Some(String::from("rescuetime-key")),
+            key_present: true,
+            block_count: 1,
+            blocks_last_fetched: now,
+            created_at: now,
+            last_login: now,
+        },
+    )
+    .unwrap();
+    let skillblock = create_skillblock(
+        conn,
+        NewSkillblock {
+            user_id: Some(user.user_id),
+            category: String::from("software"),
+            offline_category: false,
+            skill_name: String::from("Programming"),
+            skill_description: String::from("Programming skillblock"),
+            source: String::from("rescuetime"),
+            sync_status: String::from("pending"),
+        },
+    )
+    .unwrap();
+
+    (user, skillblock)
+}
+
+#[test]
+fn sync_user_skips_skillblocks_backing_off() {
+    let app = spawn_app();
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let (user, skillblock) = insert_synced_user(&conn, "auth0|backoff");
+
+    // Whole seconds, so it reads back from postgres unchanged
+    let next_attempt =
+        chrono::Local::now().date().naive_utc().and_hms(0, 0, 0) + chrono::Duration::days(1);
+
+    diesel::update(backend::db::schema::skillblocks::table.find(skillblock.block_id))
+        .set((
+            backend::db::schema::skillblocks::sync_status.eq("failed"),
+            backend::db::schema::skillblocks::sync_failures.eq(2),
+            backend::db::schema::skillblocks::next_attempt_at.eq(Some(next_attempt)),
+        ))
+        .execute(&conn)
+        .unwrap();
+
+    sync_user(&conn, &user);
+
+    // Skillblock wasn't attempted, so its backoff is left as it was
+    let skillblocks = query_skillblocks(&conn, &user).unwrap();
+
+    assert_eq!(skillblocks[0].sync_failures, 2);
+    assert_eq!(skillblocks[0].next_attempt_at, Some(next_attempt));
+}
+
+#[test]
+fn skillblocks_due_for_sync_skip_only_unreadable_credentials() {
+    let app = spawn_app();
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let (_, good_block) = insert_synced_user(&conn, "auth0|good");
+    let (bad_user, bad_block) = insert_synced_user(&conn, "auth0|bad");
+
+    // Data key wrapped with a key that's no longer configured
+    update_user_credentials(
+        &conn,
+        bad_user.user_id,
+        &CredentialChanges {
+            api_key: Some(String::from("c2VhbGVk")),
+            toggl_api_key: None,
+            wakatime_api_key: None,
+            credentials_key_id: Some(String::from("rotated-out")),
+            wrapped_data_key: Some(String::from("d3JhcHBlZA==")),
+        },
+    )
+    .unwrap();
+
+    let now = chrono::Local::now().naive_utc();
+    let due = query_skillblocks_due_for_sync(&conn, now, now).unwrap();
+
+    assert_eq!(due.len(), 2);
+    for (skillblock, user) in due {
+        if skillblock.block_id == good_block.block_id {
+            assert_eq!(user.unwrap().api_key.as_deref(), Some("rescuetime-key"));
+        } else {
+            assert_eq!(skillblock.block_id, bad_block.block_id);
+            assert!(user.is_err());
+        }
+    }
+}
+
+#[test]
+fn stored_api_key_is_sealed_and_opened_on_query() {
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let rocket_instance = app.client.rocket();
+    create_mock_skillblock(&app);
+
+    let rescuetime_api_key = std::env::var("RESCUETIME_API_KEY").unwrap();
+    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
+
+    // Read column as stored, without opening credentials
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let stored_key: Option<String> = backend::db::schema::users::table
+        .select(backend::db::schema::users::api_key)
+        .first(&conn)
+        .unwrap();
+
+    assert_eq!(user.api_key, Some(rescuetime_api_key.clone()));
+    assert!(user.wrapped_data_key.is_some());
+    assert_ne!(stored_key, Some(rescuetime_api_key));
+}
+
+#[test]
+fn delete_skillblock_returns_401_if_user_not_logged_in() {
+    let app = spawn_app();
+
+    let req = app.client.delete("/api/skillblocks/1");
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn health_check_returns_200() {
+    let app = spawn_app();
+
+    let req = app.client.get("/health_check");
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Ok)
+}
+
+#[test]
+fn process_login_successfully_stores_user_and_returns_303() {
+    // Arrange
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let response_status = config_result.status();
+    let rocket_instance = app.client.rocket();
+
+    // Retrieve user from postgres database
+    let pg_user = retrieve_user(&app, config_result, rocket_instance);
+
+    // Assert
+    assert_eq!(pg_user.is_some(), true);
+    assert_eq!(response_status, Status::SeeOther);
+}
+
+#[test]
+fn process_logout_successfully_returns_303() {
+    let app = spawn_app();
+    let _config_result = configure_testuser(&app);
+
+    let req = app.client.get("/logout");
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::SeeOther);
+}
+
+#[test]
+fn logout_all_devices_returns_204_and_removes_sessions() {
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let session_token = session_token(&config_result);
+
+    let req = app.client.delete("/api/sessions");
+    let response = req.dispatch();
+
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let remaining_sessions: i64 = backend::db::schema::sessions::table
+        .count()
+        .get_result(&conn)
+        .unwrap();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert_eq!(remaining_sessions, 0);
+
+    // Cookie of removed session no longer logs in
+    let req = app
+        .client
+        .get("/api/skillblocks")
+        .cookie(Cookie::new("session", session_token));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn stored_session_token_is_hashed() {
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let session_token = session_token(&config_result);
+
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let stored_hash: String = backend::db::schema::sessions::table
+        .select(backend::db::schema::sessions::token_hash)
+        .first(&conn)
+        .unwrap();
+
+    assert_ne!(stored_hash, session_token);
+    assert_eq!(stored_hash.len(), 64);
+}
+
+#[test]
+fn get_skillblocks_returns_401_if_session_unknown() {
+    let app = spawn_app();
+
+    let req = app
+        .client
+        .get("/api/skillblocks")
+        .cookie(rocket::http::Cookie::new("session", "unknown-session"));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+// Create api token with given scope for logged in test user
+fn create_api_token(app: &TestApp, scope: &str) -> IssuedApiToken {
+    let req = app
+        .client
+        .post("/api/tokens")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{"name": "ci dashboard", "scope": "{}"}}"#,
+            scope
+        ));
+    let mut response = req.dispatch();
+    assert_eq!(response.status(), Status::Created);
+
+    serde_json::from_str(&response.body_string().unwrap()).unwrap()
+}
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+#[test]
+fn create_api_token_returns_201_and_stores_hash() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let issued = create_api_token(&app, "read");
+
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    let stored_hash: String = backend::db::schema::api_tokens::table
+        .select(backend::db::schema::api_tokens::token_hash)
+        .first(&conn)
+        .unwrap();
+
+    assert!(issued.token.starts_with("bp_"));
+    assert_eq!(issued.api_token.scope, "read");
+    assert_ne!(stored_hash, issued.token);
+}
+
+#[test]
+fn created_api_token_location_serves_token_without_secret() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .post("/api/tokens")
+        .header(ContentType::JSON)
+        .body(r#"{"name": "ci dashboard", "scope": "read"}"#);
+    let mut response = req.dispatch();
+    let location = response.headers().get_one("Location").unwrap().to_string();
+    let issued: IssuedApiToken = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    let req = app.client.get(location);
+    let mut response = req.dispatch();
+    let body = response.body_string().unwrap();
+    let api_token: ApiToken = serde_json::from_str(&body).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(api_token.id, issued.api_token.id);
+    assert_eq!(api_token.name, "ci dashboard");
+    assert!(!body.contains(&issued.token));
+
+    let req = app
+        .client
+        .get(format!("/api/tokens/{}", issued.api_token.id + 1));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn create_api_token_returns_422_if_scope_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .post("/api/tokens")
+        .header(ContentType::JSON)
+        .body(r#"{"name": "ci dashboard", "scope": "admin"}"#);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "invalid_token_scope");
+}
+
+#[test]
+fn bearer_token_authenticates_and_records_last_use() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let issued = create_api_token(&app, "read");
+
+    let req = app.client.get("/api/export").header(bearer(&issued.token));
+    let response = req.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let req = app.client.get("/api/tokens");
+    let mut response = req.dispatch();
+    let tokens: Vec<ApiToken> = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(tokens.len(), 1);
+    assert!(tokens[0].last_used_at.is_some());
+}
+
+#[test]
+fn read_scoped_token_returns_403_on_write() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let issued = create_api_token(&app, "read");
+
+    let req = app
+        .client
+        .delete("/api/skillblocks/1")
+        .header(bearer(&issued.token));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn revoked_token_returns_401() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let issued = create_api_token(&app, "read_write");
+
+    let req = app
+        .client
+        .delete(format!("/api/tokens/{}", issued.api_token.id));
+    let response = req.dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    let req = app.client.get("/api/export").header(bearer(&issued.token));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn bearer_token_returns_403_on_token_and_account_routes() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let issued = create_api_token(&app, "read_write");
+
+    let req = app
+        .client
+        .post("/api/tokens")
+        .header(ContentType::JSON)
+        .header(bearer(&issued.token))
+        .body(r#"{"name": "minted", "scope": "read_write"}"#);
+    assert_eq!(req.dispatch().status(), Status::Forbidden);
+
+    let req = app.client.get("/api/tokens").header(bearer(&issued.token));
+    assert_eq!(req.dispatch().status(), Status::Forbidden);
+
+    let req = app
+        .client
+        .post("/api/account/deletion")
+        .header(bearer(&issued.token));
+    assert_eq!(req.dispatch().status(), Status::Forbidden);
+
+    let req = app
+        .client
+        .delete("/api/account")
+        .header(ContentType::JSON)
+        .header(bearer(&issued.token))
+        .body(r#"{"confirmation_token": "not-the-token"}"#);
+    assert_eq!(req.dispatch().status(), Status::Forbidden);
+
+    let req = app
+        .client
+        .delete("/api/sessions")
+        .header(bearer(&issued.token));
+    assert_eq!(req.dispatch().status(), Status::Forbidden);
+
+    // Account and its sessions are untouched
+    let req = app.client.get("/api/tokens");
+    assert_eq!(req.dispatch().status(), Status::Ok);
+}
+
+#[test]
+fn other_authorization_schemes_fall_back_to_session_cookie() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+
+    let req = app
+        .client
+        .get("/api/skillblocks")
+        .header(Header::new("Authorization", "Basic dXNlcjpwYXNz"));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn unknown_bearer_token_returns_401() {
+    let app = spawn_app();
+
+    let req = app
+        .client
+        .get("/api/skillblocks")
+        .header(bearer("bp_not-a-real-token"));
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn create_entry_successfully_returns_201_and_entry() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 45}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let location = response.headers().get_one("Location").map(String::from);
+    let entry: DateTime = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Created);
+    assert_eq!(
+        location,
+        Some(format!("/api/skillblocks/{}/entries/2021-03-14", block_id))
+    );
+    assert_eq!(entry.day_time, 45 * 60);
+}
+
+#[test]
+fn create_entry_returns_409_if_day_has_entry() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let entries_url = format!("/api/skillblocks/{}/entries", block_id);
+    let body = r#"{"day_date": "2021-03-14", "minutes": 45}"#;
+    app.client
+        .post(entries_url.clone())
+        .body(body)
+        .header(ContentType::JSON)
+        .dispatch();
+
+    let req = app
+        .client
+        .post(entries_url)
+        .body(body)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Conflict);
+    assert_eq!(payload.code, "entry_exists");
+}
+
+#[test]
+fn create_entry_returns_422_if_minutes_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 1441}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "invalid_minutes");
+}
+
+#[test]
+fn create_entry_returns_422_if_skillblock_synced() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 45}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "entries_not_allowed");
+}
+
+#[test]
+fn create_entry_returns_422_for_offline_category() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let rescuetime_api_key = std::env::var("RESCUETIME_API_KEY").unwrap();
+    let block_id = create_json_skillblock(
+        &app,
+        &format!(
+            r#"{{"api_key": "{}", "category": "Running", "description": "Runs", "offline_category": true, "skill_name": "Running", "source": "rescuetime"}}"#,
+            rescuetime_api_key
+        ),
+    );
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 30}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    // Offline categories are synced too, so entries would be overwritten
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "entries_not_allowed");
+}
+
+#[test]
+fn manual_entry_survives_sync() {
+    let app = spawn_app();
+    let config_result = configure_testuser(&app).unwrap();
+    let rocket_instance = app.client.rocket();
+    let block_id = create_manual_skillblock(&app);
+    let user = retrieve_user(&app, config_result, rocket_instance).unwrap();
+
+    app.client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 45}"#)
+        .header(ContentType::JSON)
+        .dispatch();
+
+    let conn =
+        PgConnection::establish(&app.pg_connection).expect("Error connecting to postgres database");
+    sync_user(&conn, &user);
+
+    let day_times: Vec<i32> = backend::db::schema::date_times::table
+        .filter(backend::db::schema::date_times::block_id.eq(block_id))
+        .select(backend::db::schema::date_times::day_time)
+        .load(&conn)
+        .unwrap();
+
+    assert_eq!(day_times, vec![45 * 60]);
+}
+
+#[test]
+fn update_entry_successfully_returns_200_and_entry() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    app.client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 45}"#)
+        .header(ContentType::JSON)
+        .dispatch();
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}/entries/2021-03-14", block_id))
+        .body(r#"{"minutes": 90}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let entry: DateTime = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(entry.day_time, 90 * 60);
+}
+
+#[test]
+fn update_entry_returns_404_if_entry_not_found() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let req = app
+        .client
+        .put(format!("/api/skillblocks/{}/entries/2021-03-14", block_id))
+        .body(r#"{"minutes": 90}"#)
+        .header(ContentType::JSON);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(payload.code, "entry_not_found");
+}
+
+#[test]
+fn delete_entry_successfully_returns_204() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    app.client
+        .post(format!("/api/skillblocks/{}/entries", block_id))
+        .body(r#"{"day_date": "2021-03-14", "minutes": 45}"#)
+        .header(ContentType::JSON)
+        .dispatch();
+
+    let entry_url = format!("/api/skillblocks/{}/entries/2021-03-14", block_id);
+    let response = app.client.delete(entry_url.clone()).dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    // Entry is gone, so deleting again finds nothing
+    let response = app.client.delete(entry_url).dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn delete_entry_returns_401_if_user_not_logged_in() {
+    let app = spawn_app();
+
+    let req = app.client.delete("/api/skillblocks/1/entries/2021-03-14");
+    let response = req.dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn import_time_data_returns_200_and_report() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/import", block_id))
+        .body("date,minutes\n2021-03-01,30\n2021-03-02,ten\n")
+        .header(ContentType::CSV);
+    let mut response = req.dispatch();
+    let report: ImportReport = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(report.inserted, 1);
+    assert_eq!(report.errors.len(), 1);
+}
+
+#[test]
+fn import_time_data_returns_413_if_body_too_large() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    // Rows past limit would otherwise be cut off and imported partially
+    let body = "2021-03-01,120\n".repeat(70_000);
+    let req = app
+        .client
+        .post(format!(
+            "/api/skillblocks/{}/import?on_duplicate=replace",
+            block_id
+        ))
+        .body(body)
+        .header(ContentType::CSV);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+    assert_eq!(payload.code, "import_too_large");
+}
+
+#[test]
+fn import_time_data_returns_400_if_on_duplicate_invalid() {
+    let app = spawn_app();
+    configure_testuser(&app).unwrap();
+    let block_id = create_manual_skillblock(&app);
+
+    let req = app
+        .client
+        .post(format!(
+            "/api/skillblocks/{}/import?on_duplicate=replce",
+            block_id
+        ))
+        .body("date,minutes\n2021-03-14,45\n")
+        .header(ContentType::CSV);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(payload.code, "invalid_on_duplicate");
+}
+
+#[test]
+fn import_time_data_returns_422_if_skillblock_synced() {
+    let app = spawn_app();
+    let (_, block_id) = logged_in_block(&app);
+
+    let req = app
+        .client
+        .post(format!("/api/skillblocks/{}/import", block_id))
+        .body("date,minutes\n2021-03-14,45\n")
+        .header(ContentType::CSV);
+    let mut response = req.dispatch();
+    let payload: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(payload.code, "import_not_allowed");
 }
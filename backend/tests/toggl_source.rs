@@ -0,0 +1,117 @@
+mod common;
+
+use backend::sources::{TimeSource, Toggl};
+use chrono::NaiveDate;
+
+const ME_BODY: &str = r#"{"id": 1, "timezone": "America/Los_Angeles"}"#;
+
+const PROJECTS_BODY: &str = r#"[
+    {"id": 101, "name": "Guitar"},
+    {"id": 202, "name": "Running"}
+]"#;
+
+// Toggl reports entry start times in UTC. Fake user tracks time in
+// Los Angeles, seven hours behind UTC in July
+const TIME_ENTRIES_BODY: &str = r#"[
+    {"start": "2021-07-01T06:30:00+00:00", "duration": 1200, "project_id": 101, "tags": []},
+    {"start": "2021-07-01T16:00:00+00:00", "duration": 1800, "project_id": 101, "tags": ["practice"]},
+    {"start": "2021-07-02T01:30:00+00:00", "duration": 600, "project_id": 101, "tags": null},
+    {"start": "2021-07-02T03:00:00+00:00", "duration": 300, "project_id": 101, "tags": []},
+    {"start": "2021-07-02T14:00:00+00:00", "duration": 2400, "project_id": 202, "tags": ["practice"]},
+    {"start": "2021-07-03T17:00:00+00:00", "duration": -1625130000, "project_id": 101, "tags": []},
+    {"start": "2021-07-09T17:00:00+00:00", "duration": 900, "project_id": 101, "tags": []}
+]"#;
+
+// Spawn fake Toggl api server answering /me, /me/projects and
+// /me/time_entries with fixed payloads
+fn spawn_fake_toggl() -> String {
+    common::spawn_fake_api(
+        vec![
+            ("GET /me HTTP", ME_BODY),
+            ("GET /me/projects", PROJECTS_BODY),
+            ("GET /me/time_entries", TIME_ENTRIES_BODY),
+        ],
+        |request| request.to_lowercase().contains("authorization: basic"),
+    )
+}
+
+#[test]
+fn toggl_sums_project_durations_by_day() {
+    let base_url = spawn_fake_toggl();
+    let toggl = Toggl::with_base_url(String::from("test_token"), base_url);
+
+    let totals = toggl
+        .fetch_daily_totals(
+            "project:Guitar",
+            NaiveDate::from_ymd(2021, 7, 1),
+            NaiveDate::from_ymd(2021, 7, 7),
+        )
+        .unwrap();
+
+    // Running entries and entries outside requested window are skipped.
+    // Evening entries stay on the day they were tracked in Los Angeles
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 1)), Some(&2700));
+}
+
+#[test]
+fn toggl_buckets_entries_crossing_midnight_by_toggl_timezone() {
+    let base_url = spawn_fake_toggl();
+    let toggl = Toggl::with_base_url(String::from("test_token"), base_url);
+
+    let totals = toggl
+        .fetch_daily_totals(
+            "project:Guitar",
+            NaiveDate::from_ymd(2021, 6, 30),
+            NaiveDate::from_ymd(2021, 7, 1),
+        )
+        .unwrap();
+
+    // Entry started on July 1st in UTC but June 30th in Los Angeles,
+    // while entries started on July 2nd in UTC belong to July 1st
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 6, 30)), Some(&1200));
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 1)), Some(&2700));
+}
+
+#[test]
+fn toggl_limits_history_to_last_ninety_days() {
+    let toggl = Toggl::new(String::from("test_token"));
+
+    assert_eq!(
+        toggl.earliest_date(NaiveDate::from_ymd(2021, 7, 1)),
+        Some(NaiveDate::from_ymd(2021, 4, 2))
+    );
+}
+
+#[test]
+fn toggl_sums_tag_durations_by_day() {
+    let base_url = spawn_fake_toggl();
+    let toggl = Toggl::with_base_url(String::from("test_token"), base_url);
+
+    let totals = toggl
+        .fetch_daily_totals(
+            "tag:practice",
+            NaiveDate::from_ymd(2021, 7, 1),
+            NaiveDate::from_ymd(2021, 7, 7),
+        )
+        .unwrap();
+
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 1)), Some(&1800));
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 2)), Some(&2400));
+}
+
+#[test]
+fn toggl_returns_error_for_unknown_project() {
+    let base_url = spawn_fake_toggl();
+    let toggl = Toggl::with_base_url(String::from("test_token"), base_url);
+
+    let result = toggl.fetch_daily_totals(
+        "Painting",
+        NaiveDate::from_ymd(2021, 7, 1),
+        NaiveDate::from_ymd(2021, 7, 7),
+    );
+
+    assert!(result.is_err());
+}
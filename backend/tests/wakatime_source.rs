@@ -0,0 +1,82 @@
+mod common;
+
+use backend::sources::{TimeSource, WakaTime};
+use chrono::NaiveDate;
+
+const SUMMARIES_BODY: &str = r#"{
+    "data": [
+        {
+            "range": {"date": "2021-07-01"},
+            "languages": [{"name": "Rust", "total_seconds": 3600.4}, {"name": "Python", "total_seconds": 120.0}],
+            "projects": [{"name": "blockplot", "total_seconds": 3720.4}]
+        },
+        {
+            "range": {"date": "2021-07-02"},
+            "languages": [{"name": "Python", "total_seconds": 900.0}],
+            "projects": [{"name": "scripts", "total_seconds": 900.0}]
+        },
+        {
+            "range": {"date": "2021-07-03"},
+            "languages": [],
+            "projects": []
+        }
+    ]
+}"#;
+
+// Spawn fake WakaTime api server answering summaries
+// requests with a fixed payload
+fn spawn_fake_wakatime() -> String {
+    common::spawn_fake_api(
+        vec![("GET /users/current/summaries", SUMMARIES_BODY)],
+        |request| request.contains("api_key=test_key"),
+    )
+}
+
+#[test]
+fn wakatime_reads_project_seconds_by_day() {
+    let base_url = spawn_fake_wakatime();
+    let wakatime = WakaTime::with_base_url(String::from("test_key"), base_url);
+
+    let totals = wakatime
+        .fetch_daily_totals(
+            "project:BlockPlot",
+            NaiveDate::from_ymd(2021, 7, 1),
+            NaiveDate::from_ymd(2021, 7, 3),
+        )
+        .unwrap();
+
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 1)), Some(&3720));
+}
+
+#[test]
+fn wakatime_reads_language_seconds_by_day() {
+    let base_url = spawn_fake_wakatime();
+    let wakatime = WakaTime::with_base_url(String::from("test_key"), base_url);
+
+    let totals = wakatime
+        .fetch_daily_totals(
+            "language:python",
+            NaiveDate::from_ymd(2021, 7, 1),
+            NaiveDate::from_ymd(2021, 7, 3),
+        )
+        .unwrap();
+
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 1)), Some(&120));
+    assert_eq!(totals.get(&NaiveDate::from_ymd(2021, 7, 2)), Some(&900));
+}
+
+#[test]
+fn wakatime_returns_error_for_rejected_key() {
+    let base_url = spawn_fake_wakatime();
+    let wakatime = WakaTime::with_base_url(String::from("wrong_key"), base_url);
+
+    let result = wakatime.fetch_daily_totals(
+        "language:Rust",
+        NaiveDate::from_ymd(2021, 7, 1),
+        NaiveDate::from_ymd(2021, 7, 3),
+    );
+
+    assert!(result.is_err());
+}
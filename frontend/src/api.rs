@@ -1,5 +1,6 @@
-use crate::types::{Session, TimeWrapper};
+use crate::types::{ErrorBody, GoalProgress, GridView, Session, SkillblockStats, TimeWrapper};
 use anyhow::Error;
+use serde::Deserialize;
 use yew::callback::Callback;
 use yew::format::{Json, Nothing};
 use yew::services::fetch::{FetchOptions, FetchService, FetchTask, Request, Response};
@@ -8,9 +9,42 @@ use yew::web_sys::RequestCredentials;
 pub type FetchResponse<T> = Response<Json<Result<T, Error>>>;
 type FetchCallback<T> = Callback<FetchResponse<T>>;
 
-// A testing request getter. Will probably remove at conclusion of tests
-pub fn get_dev_skillblocks(callback: FetchCallback<TimeWrapper>) -> FetchTask {
-    let url = format!("http://localhost:8000/api/skillblocks");
+// Body of backend api response. Holds either the requested
+// payload or the error reported by the backend
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ApiResponse<T> {
+    Success(T),
+    Failure(ErrorBody),
+}
+
+// A testing request getter. Will probably remove at conclusion of tests.
+// Query limits time data to span shown by block grid
+pub fn get_dev_skillblocks(
+    query: String,
+    callback: FetchCallback<ApiResponse<TimeWrapper>>,
+) -> FetchTask {
+    let url = format!("http://localhost:8000/api/skillblocks?{}", query);
+    let request = Request::get(url).body(Nothing).unwrap();
+    let options = FetchOptions {
+        credentials: Some(RequestCredentials::Include),
+        ..FetchOptions::default()
+    };
+
+    FetchService::fetch_binary_with_options(request, options, callback).unwrap()
+}
+
+// Fetch streaks, averages and totals of a skillblock over
+// the same span as its time data
+pub fn get_skillblock_stats(
+    block_id: i32,
+    query: String,
+    callback: FetchCallback<ApiResponse<SkillblockStats>>,
+) -> FetchTask {
+    let url = format!(
+        "http://localhost:8000/api/skillblocks/{}/stats?{}",
+        block_id, query
+    );
     let request = Request::get(url).body(Nothing).unwrap();
     let options = FetchOptions {
         credentials: Some(RequestCredentials::Include),
@@ -20,6 +54,45 @@ pub fn get_dev_skillblocks(callback: FetchCallback<TimeWrapper>) -> FetchTask {
     FetchService::fetch_binary_with_options(request, options, callback).unwrap()
 }
 
+// Fetch goal of a skillblock with its progress over the same span
+// as its time data. Backend responds with goal_not_found when no goal is set
+pub fn get_skillblock_goal(
+    block_id: i32,
+    query: String,
+    callback: FetchCallback<ApiResponse<GoalProgress>>,
+) -> FetchTask {
+    let url = format!(
+        "http://localhost:8000/api/skillblocks/{}/goal?{}",
+        block_id, query
+    );
+    let request = Request::get(url).body(Nothing).unwrap();
+    let options = FetchOptions {
+        credentials: Some(RequestCredentials::Include),
+        ..FetchOptions::default()
+    };
+
+    FetchService::fetch_binary_with_options(request, options, callback).unwrap()
+}
+
+// Link to png of skillblock grid and stats. Backend serves it as a
+// download, so it's opened as a plain link rather than fetched
+pub fn grid_png_url(block_id: i32, view: GridView) -> String {
+    match view {
+        GridView::Rolling => format!(
+            "http://localhost:8000/api/skillblocks/{}/grid.png",
+            block_id
+        ),
+        GridView::Year(year) => format!(
+            "http://localhost:8000/api/skillblocks/{}/grid.png?year={}",
+            block_id, year
+        ),
+    }
+}
+
+// Link to zip archive of all user data. Opened as a plain link, since
+// backend serves it as a download
+pub const EXPORT_URL: &str = "http://localhost:8000/api/export";
+
 // Fetch user session information
 pub fn get_user_session(callback: FetchCallback<Session>) -> FetchTask {
     let url = format!("http://localhost:8000/home");
@@ -10,6 +10,7 @@ pub struct Form {
 
 pub enum Msg {
     PostData,
+    SelectSource(String),
     ToggleCategory,
 }
 
@@ -19,12 +20,51 @@ pub struct Props {
 }
 
 struct State {
+    source: String,
     toggle_category: bool,
 }
 
 impl Form {
     fn api_key_view(&self) -> Html {
-        if !self.props.key_present {
+        if self.state.source == "toggl" {
+            html! {
+                <Field>
+                    <label class="label">{ "Toggl Api Token" }</label>
+                    <p>
+                        {
+                            "Grab your api token from the bottom of your Toggl Track profile page and toss it in below.
+                            If you've given us one before, you can leave this blank"
+                        }
+                    </p>
+                    <Control>
+                        <input
+                            class="input"
+                            name="toggl_api_key"
+                            placeholder="Text input"
+                        />
+                    </Control>
+                </Field>
+            }
+        } else if self.state.source == "wakatime" {
+            html! {
+                <Field>
+                    <label class="label">{ "WakaTime Api Key" }</label>
+                    <p>
+                        {
+                            "Grab your secret api key from your WakaTime account settings page and toss it in below.
+                            If you've given us one before, you can leave this blank"
+                        }
+                    </p>
+                    <Control>
+                        <input
+                            class="input"
+                            name="wakatime_api_key"
+                            placeholder="Text input"
+                        />
+                    </Control>
+                </Field>
+            }
+        } else if !self.props.key_present && self.state.source == "rescuetime" {
             html! {
                 <Field>
                     <label class="label">{ "RescueTime Api Key" }</label>
@@ -52,6 +92,13 @@ impl Form {
     }
 
     fn offline_category_view(&self) -> Html {
+        if self.state.source != "rescuetime" {
+            return html! {
+                <>
+                </>
+            };
+        }
+
         html! {
             <Field>
                 <Control>
@@ -74,7 +121,61 @@ impl Form {
     }
 
     fn skill_category_view(&self) -> Html {
-        if !self.state.toggle_category {
+        if self.state.source == "toggl" {
+            html! {
+                <Field>
+                    <label class="label">{ "Toggl Project Or Tag" }</label>
+                    <p>
+                        {
+                            "Type the name of the Toggl project to track. To track a tag instead, prefix the name with \"tag:\""
+                        }
+                    </p>
+                    <Control>
+                        <input
+                            class="input"
+                            name="category"
+                            placeholder="Project name or tag:name"
+                        />
+                    </Control>
+                </Field>
+            }
+        } else if self.state.source == "wakatime" {
+            html! {
+                <Field>
+                    <label class="label">{ "WakaTime Project Or Language" }</label>
+                    <p>
+                        {
+                            "Type the name of the WakaTime project to track. To track a language instead, prefix the name with \"language:\""
+                        }
+                    </p>
+                    <Control>
+                        <input
+                            class="input"
+                            name="category"
+                            placeholder="Project name or language:name"
+                        />
+                    </Control>
+                </Field>
+            }
+        } else if self.state.source == "manual" {
+            html! {
+                <Field>
+                    <label class="label">{ "Skill Category" }</label>
+                    <p>
+                        {
+                            "Time for manual skillblocks is logged by hand, so any category name you like will do"
+                        }
+                    </p>
+                    <Control>
+                        <input
+                            class="input"
+                            name="category"
+                            placeholder="Category Input"
+                        />
+                    </Control>
+                </Field>
+            }
+        } else if !self.state.toggle_category {
             html! {
                 <Field>
                     <label class="label">{ "Skill Category" }</label>
@@ -137,6 +238,35 @@ impl Form {
         }
     }
 
+    fn source_view(&self) -> Html {
+        html! {
+            <Field>
+                <label class="label">{ "Time Source" }</label>
+                <p>
+                    {
+                        "Pick the service you use to keep track of time spent on this skill"
+                    }
+                </p>
+                <Control>
+                    <div class="select">
+                        <select
+                            name="source"
+                            onchange=self.link.callback(|data: ChangeData| match data {
+                                ChangeData::Select(select) => Msg::SelectSource(select.value()),
+                                _ => Msg::SelectSource(String::from("rescuetime")),
+                            })
+                        >
+                            <option value="rescuetime" selected=true>{ "RescueTime" }</option>
+                            <option value="toggl">{ "Toggl Track" }</option>
+                            <option value="wakatime">{ "WakaTime" }</option>
+                            <option value="manual">{ "Manual Entry" }</option>
+                        </select>
+                    </div>
+                </Control>
+            </Field>
+        }
+    }
+
     fn skill_name_view(&self) -> Html {
         html! {
             <Field>
@@ -167,6 +297,7 @@ impl Component for Form {
             link: _link,
             props,
             state: State {
+                source: String::from("rescuetime"),
                 toggle_category: false,
             },
         }
@@ -179,6 +310,12 @@ impl Component for Form {
 
                 true
             }
+            Msg::SelectSource(source) => {
+                self.state.source = source;
+                self.state.toggle_category = false;
+
+                true
+            }
             Msg::ToggleCategory => {
                 self.state.toggle_category = !self.state.toggle_category;
 
@@ -201,6 +338,7 @@ impl Component for Form {
                             <p class="title is-3">{ "Let's create a skillblock!" }</p>
                             <div class="column is-half">
                                 <form action="http://localhost:8000/api/new_skillblock" method="POST">
+                                    { self.source_view() }
                                     { self.api_key_view() }
                                     { self.skill_name_view() }
                                     { self.offline_category_view() }
@@ -6,11 +6,16 @@ use yew_router::agent::RouteRequest;
 use yew_router::prelude::*;
 
 use crate::api;
+use crate::api::ApiResponse;
 use crate::route::Route::UnauthorizedPage;
-use crate::types::{Color, TimeData, TimeStats, TimeWrapper};
+use crate::types::{
+    Color, ErrorBody, GoalProgress, GridView, SkillblockStats, TimeData, TimeWrapper,
+};
 
 use num_traits::FromPrimitive;
 
+use std::collections::{HashMap, HashSet};
+
 use ybc::TileCtx::{Ancestor, Child, Parent};
 use ybc::TileSize;
 use ybc::{Box, Container, Section, Tile};
@@ -24,6 +29,12 @@ pub enum Msg {
     GetDevSkillBlock,
     GetSkillBlocksSuccess(TimeWrapper),
     GetSkillBlocksError(Error),
+    GetSkillBlocksFailure(ErrorBody),
+    GetStatsSuccess(i32, SkillblockStats),
+    GetStatsError(String),
+    GetGoalSuccess(i32, GoalProgress),
+    GetGoalFailure(ErrorBody),
+    ShowView(GridView),
     UnauthorizedAccess,
 }
 
@@ -32,12 +43,17 @@ pub struct User {
     link: ComponentLink<Self>,
     router: RouteAgentDispatcher<()>,
     task: Option<FetchTask>,
+    block_tasks: Vec<FetchTask>,
 }
 
 struct State {
     skill_blocks: Vec<TimeData>,
     get_skillblocks_error: Option<Error>,
+    get_skillblocks_failure: Option<ErrorBody>,
     get_skillblocks_loaded: bool,
+    stats: HashMap<i32, SkillblockStats>,
+    goal_met_days: HashMap<i32, HashSet<NaiveDate>>,
+    view: GridView,
 }
 
 impl User {
@@ -52,22 +68,8 @@ impl User {
         // create empty vector representing days of a week
         let mut day_elements = Vec::new();
 
-        let mut time_stats = TimeStats {
-            daily_max: 0,
-            yearly_max: 0,
-            longest_chain: 0,
-        };
-
-        // Count of consective days having time data
-        let mut chain_count = 0;
-
-        // Create vector of timestamps for one year
+        // Create vector of timestamps for span of selected view
         let current_date = Local::now().date().naive_utc();
-        let (current_year, current_month, current_day) = (
-            current_date.year(),
-            current_date.month(),
-            current_date.day(),
-        );
 
         // Calculate value to subtract from current day. When new week starts on a sunday,
         // 0 is subtracted from current day, shifting calender graph leftward and replacing oldest week
@@ -86,15 +88,10 @@ impl User {
         //     current_day - week_incrementor
         // };
 
-        // Current workaround uses current day for graph starting square
-        let year_start = NaiveDateTime::new(
-            NaiveDate::from_ymd(current_year - 1, current_month, current_day),
-            NaiveTime::from_hms(0, 0, 0),
-        );
-        let year_end = NaiveDateTime::new(
-            NaiveDate::from_ymd(current_year, current_month, current_day),
-            NaiveTime::from_hms(0, 0, 0),
-        );
+        // Current workaround uses first day of view for graph starting square
+        let (start_date, end_date) = self.state.view.range(current_date);
+        let year_start = NaiveDateTime::new(start_date, NaiveTime::from_hms(0, 0, 0));
+        let year_end = NaiveDateTime::new(end_date, NaiveTime::from_hms(0, 0, 0));
         let mut selected_day = year_start;
         let mut year = Vec::new();
         while selected_day <= year_end {
@@ -102,9 +99,22 @@ impl User {
             selected_day = selected_day + Duration::days(1);
         }
 
+        // Days counting towards a met goal get outlined
+        let no_goal = HashSet::new();
+        let goal_met_days = self
+            .state
+            .goal_met_days
+            .get(&time_block.block_id)
+            .unwrap_or(&no_goal);
+
         // Iterate through vector of timestamps and build grid item
         for day in &year {
-            let mut color = Color::NEUTRAL;
+            let mut color = time_block.appearance.color_for(0);
+            let stroke = if goal_met_days.contains(&day.date()) {
+                Color::GOAL_MET
+            } else {
+                "none"
+            };
             let weekday = day.weekday();
             let formatted_date = day.format("%Y-%m-%d");
             if weekday == Weekday::Sun {
@@ -137,43 +147,13 @@ impl User {
             if let Some(value) = time_block.time_data.get(&day) {
                 let minutes = value / 60;
 
-                // Time statistics calculations
-                if minutes > time_stats.daily_max {
-                    time_stats.daily_max = minutes;
-                }
-                time_stats.yearly_max += minutes;
-
-                // Represent time data as color
-                match minutes {
-                    0 => color = Color::NEUTRAL,
-                    1..=15 => color = Color::LIGHT,
-                    16..=30 => color = Color::LIGHTMEDIUM,
-                    31..=45 => color = Color::MEDIUM,
-                    46..=60 => color = Color::MEDIUMHIGH,
-                    _ => color = Color::HIGH,
-                }
-
-                // Calculate length of longest chain of days that have time data
-                match color {
-                    Color::NEUTRAL => {
-                        if chain_count != 0 {
-                            if chain_count > time_stats.longest_chain {
-                                time_stats.longest_chain = chain_count;
-                                chain_count = 0;
-                            } else {
-                                chain_count = 0;
-                            }
-                        }
-                    }
-                    _ => {
-                        chain_count += 1;
-                    }
-                }
+                // Represent time data as color of its shade
+                color = time_block.appearance.color_for(minutes);
             }
 
             // Create <rect> element representing a day
             let day_element = html! {
-                <rect width="11" height="11" y=weekday.num_days_from_sunday() * 15 rx=2 ry=2 fill=color style="outline: 1px solid #1b1f230a; outline-offset: -1px;" date-data=formatted_date></rect>
+                <rect width="11" height="11" y=weekday.num_days_from_sunday() * 15 rx=2 ry=2 fill=color stroke=stroke stroke-width=2 style="outline: 1px solid #1b1f230a; outline-offset: -1px;" date-data=formatted_date></rect>
             };
             day_elements.push(day_element);
 
@@ -191,13 +171,6 @@ impl User {
             }
         }
 
-        // Time stats for hours/minutes labels
-        let year_minutes_average = time_stats.yearly_max / 365;
-        let year_hours_average = year_minutes_average / 60;
-        let year_minutes_remainder = year_minutes_average % 60;
-        let max_hours = time_stats.daily_max / 60;
-        let max_minutes = time_stats.daily_max % 60;
-
         // Create svg container, collect grid elements and append to <g> tag. Append week/month labels
         let html_element = html! {
             <>
@@ -216,39 +189,162 @@ impl User {
                         </g>
                     </svg>
                 </Box>
-                <nav class="level">
-                    <div class="level-item has-text-centered">
-                        <div>
-                            <p class="heading">{ "Average Daily Time" }</p>
-                            <p class="title">{ year_hours_average }</p>
-                            <p class="heading">{"hour(s)"}</p>
-                            <p class="title">{ year_minutes_remainder }</p>
-                            <p class="heading">{ "minute(s)" }</p>
-                        </div>
+                { self.view_stats(time_block.block_id) }
+            </>
+        };
+
+        html_element
+    }
+
+    // Create labels for stats of skillblock served by backend
+    fn view_stats(&self, block_id: i32) -> Html {
+        let stats = match self.state.stats.get(&block_id) {
+            Some(stats) => stats,
+            None => return html! {},
+        };
+
+        // Time stats for hours/minutes labels
+        let mean_minutes = stats.daily_mean / 60;
+        let median_minutes = stats.daily_median / 60;
+        let max_minutes = stats.daily_max / 60;
+        let longest_dates = match (stats.longest_streak.start, stats.longest_streak.end) {
+            (Some(start), Some(end)) => {
+                format!("{} - {}", start.format("%b %e"), end.format("%b %e"))
+            }
+            _ => String::new(),
+        };
+
+        html! {
+            <nav class="level">
+                <div class="level-item has-text-centered">
+                    <div>
+                        <p class="heading">{ "Average Daily Time" }</p>
+                        <p class="title">{ mean_minutes / 60 }</p>
+                        <p class="heading">{"hour(s)"}</p>
+                        <p class="title">{ mean_minutes % 60 }</p>
+                        <p class="heading">{ "minute(s)" }</p>
+                        <p class="heading">{ format!("median {}h {}m", median_minutes / 60, median_minutes % 60) }</p>
                     </div>
-                    <div class="level-item has-text-centered">
-                        <div>
-                            <p class="heading">{ "Max Time In A Day" }</p>
-                            <p class="title">{ max_hours }</p>
-                            <p class="heading">{"hour(s)"}</p>
-                            <p class="title">{ max_minutes }</p>
-                            <p class="heading">{ "minute(s)" }</p>
-                        </div>
+                </div>
+                <div class="level-item has-text-centered">
+                    <div>
+                        <p class="heading">{ "Max Time In A Day" }</p>
+                        <p class="title">{ max_minutes / 60 }</p>
+                        <p class="heading">{"hour(s)"}</p>
+                        <p class="title">{ max_minutes % 60 }</p>
+                        <p class="heading">{ "minute(s)" }</p>
                     </div>
-                    <div class="level-item has-text-centered">
-                        <div>
-                            <p class="heading">{ "Longest Day Chain" }</p>
-                            <p class="title">{ time_stats.longest_chain }</p>
-                            <p class="heading">{ "day(s)" }</p>
-                            <p class="title">{ chain_count }</p>
-                            <p class="heading">{ "current day chain" }</p>
-                        </div>
+                </div>
+                <div class="level-item has-text-centered">
+                    <div>
+                        <p class="heading">{ "Longest Day Chain" }</p>
+                        <p class="title">{ stats.longest_streak.days }</p>
+                        <p class="heading">{ "day(s)" }</p>
+                        <p class="heading">{ longest_dates }</p>
+                        <p class="title">{ stats.current_streak.days }</p>
+                        <p class="heading">{ "current day chain" }</p>
                     </div>
-                </nav>
-            </>
+                </div>
+            </nav>
+        }
+    }
+
+    // Create link downloading grid and stats of skillblock as a png image
+    fn view_png_download(&self, block: &TimeData) -> Html {
+        html! {
+            <a class="button is-small is-light" href=api::grid_png_url(block.block_id, self.state.view)>
+                { "Download PNG" }
+            </a>
+        }
+    }
+
+    // Create "last updated" note for skillblocks synced from a time source
+    fn view_sync_status(&self, block: &TimeData) -> Html {
+        if block.sync_status == "manual" {
+            return html! {};
+        }
+
+        let last_updated = match block.last_synced_at {
+            Some(synced_at) => synced_at.format("%b %e, %Y %H:%M UTC").to_string(),
+            None => String::from("Not synced yet"),
+        };
+        let status_note = match block.sync_status.as_str() {
+            "partial" => " (some days still missing)",
+            "failed" => " (last sync failed)",
+            _ => "",
         };
 
-        html_element
+        html! {
+            <p class="is-size-7">{ format!("Last updated: {}{}", last_updated, status_note) }</p>
+        }
+    }
+
+    // Create controls for switching between calendar years and rolling view
+    fn view_navigation(&self) -> Html {
+        let current_year = Local::now().date().naive_utc().year();
+        let (label, prev_year, next_year) = match self.state.view {
+            GridView::Rolling => (String::from("Past 12 months"), current_year, None),
+            GridView::Year(year) => (
+                year.to_string(),
+                year - 1,
+                Some(year + 1).filter(|next| *next <= current_year),
+            ),
+        };
+
+        let prev = self
+            .link
+            .callback(move |_| Msg::ShowView(GridView::Year(prev_year)));
+        let next = match next_year {
+            Some(year) => html! {
+                <button class="button" onclick=self.link.callback(move |_| Msg::ShowView(GridView::Year(year)))>{ format!("{} ›", year) }</button>
+            },
+            None => html! {
+                <button class="button" disabled=true>{ "›" }</button>
+            },
+        };
+        let rolling = self.link.callback(|_| Msg::ShowView(GridView::Rolling));
+
+        html! {
+            <nav class="level">
+                <div class="level-left">
+                    <div class="level-item">
+                        <button class="button" onclick=prev>{ format!("‹ {}", prev_year) }</button>
+                    </div>
+                    <div class="level-item">
+                        <p class="title is-4">{ label }</p>
+                    </div>
+                    <div class="level-item">
+                        { next }
+                    </div>
+                </div>
+                <div class="level-right">
+                    <div class="level-item">
+                        <button class="button" onclick=rolling disabled=self.state.view == GridView::Rolling>{ "Past 12 months" }</button>
+                    </div>
+                    <div class="level-item">
+                        <a class="button is-light" href=api::EXPORT_URL>{ "Export my data" }</a>
+                    </div>
+                </div>
+            </nav>
+        }
+    }
+
+    // Create notice explaining why no skillblocks could be shown
+    fn view_failure(&self) -> Html {
+        let message = match &self.state.get_skillblocks_failure {
+            Some(error) => match error.code.as_str() {
+                "missing_api_key" => {
+                    "Add a time source api key while creating your first skillblock to get started"
+                }
+                "no_skillblocks" => "You don't have any skillblocks yet",
+                _ => error.message.as_str(),
+            },
+            None => return html! {},
+        };
+
+        html! {
+            <div class="notification is-warning">{ message }</div>
+        }
     }
 
     // Create skill block item.
@@ -266,6 +362,8 @@ impl User {
                             <p class="subtitle is-5">{ &block.category }</p>
                             <p class="title is-3">{ "Description:" }</p>
                             <p class="subtitle is-5">{ &block.skill_description }</p>
+                            { self.view_sync_status(block) }
+                            { self.view_png_download(block) }
                         </Tile>
                     </Tile>
                     <Tile ctx=Parent size=TileSize::Eight>
@@ -282,6 +380,8 @@ impl User {
 
         html! {
             <Container>
+                { self.view_navigation() }
+                { self.view_failure() }
                 { block_elements.into_iter().collect::<Html>() }
             </Container>
         }
@@ -301,11 +401,16 @@ impl Component for User {
             state: State {
                 skill_blocks,
                 get_skillblocks_error: None,
+                get_skillblocks_failure: None,
                 get_skillblocks_loaded: false,
+                stats: HashMap::new(),
+                goal_met_days: HashMap::new(),
+                view: GridView::Rolling,
             },
             link,
             router: RouteAgentDispatcher::new(),
             task: None,
+            block_tasks: Vec::new(),
         }
     }
 
@@ -314,21 +419,27 @@ impl Component for User {
             // Testing logic, may remove
             Msg::GetDevSkillBlock => {
                 self.state.get_skillblocks_loaded = false;
-                let handler =
-                    self.link
-                        .callback(move |response: api::FetchResponse<TimeWrapper>| {
-                            let (meta, Json(data)) = response.into_parts();
-                            // Check for 401, redirect to UnauthorizedPage route if true
-                            if meta.status == StatusCode::UNAUTHORIZED {
-                                Msg::UnauthorizedAccess
-                            } else {
-                                match data {
-                                    Ok(skillblocks) => Msg::GetSkillBlocksSuccess(skillblocks),
-                                    Err(error) => Msg::GetSkillBlocksError(error),
+                let handler = self.link.callback(
+                    move |response: api::FetchResponse<ApiResponse<TimeWrapper>>| {
+                        let (meta, Json(data)) = response.into_parts();
+                        // Check for 401, redirect to UnauthorizedPage route if true
+                        if meta.status == StatusCode::UNAUTHORIZED {
+                            Msg::UnauthorizedAccess
+                        } else {
+                            match data {
+                                Ok(ApiResponse::Success(skillblocks)) => {
+                                    Msg::GetSkillBlocksSuccess(skillblocks)
                                 }
+                                Ok(ApiResponse::Failure(error)) => {
+                                    Msg::GetSkillBlocksFailure(error)
+                                }
+                                Err(error) => Msg::GetSkillBlocksError(error),
                             }
-                        });
-                self.task = Some(api::get_dev_skillblocks(handler));
+                        }
+                    },
+                );
+                let query = self.state.view.query(Local::now().date().naive_utc());
+                self.task = Some(api::get_dev_skillblocks(query, handler));
                 true
             }
             Msg::GetSkillBlocksError(error) => {
@@ -336,14 +447,95 @@ impl Component for User {
                 self.state.get_skillblocks_loaded = true;
                 true
             }
+            Msg::GetSkillBlocksFailure(error) => {
+                self.state.get_skillblocks_failure = Some(error);
+                self.state.get_skillblocks_loaded = true;
+                true
+            }
             Msg::GetSkillBlocksSuccess(skillblocks) => {
+                let query = self.state.view.query(Local::now().date().naive_utc());
                 for skillblock in skillblocks.data {
+                    // Request stats covering same span as time data of skillblock
+                    let block_id = skillblock.block_id;
+                    let handler = self.link.callback(
+                        move |response: api::FetchResponse<ApiResponse<SkillblockStats>>| {
+                            let Json(data) = response.into_body();
+                            match data {
+                                Ok(ApiResponse::Success(stats)) => {
+                                    Msg::GetStatsSuccess(block_id, stats)
+                                }
+                                Ok(ApiResponse::Failure(error)) => {
+                                    Msg::GetStatsError(error.message)
+                                }
+                                Err(error) => Msg::GetStatsError(error.to_string()),
+                            }
+                        },
+                    );
+                    self.block_tasks.push(api::get_skillblock_stats(
+                        block_id,
+                        query.clone(),
+                        handler,
+                    ));
+
+                    // Request goal progress used to mark goal-met days on grid
+                    let handler = self.link.callback(
+                        move |response: api::FetchResponse<ApiResponse<GoalProgress>>| {
+                            let Json(data) = response.into_body();
+                            match data {
+                                Ok(ApiResponse::Success(progress)) => {
+                                    Msg::GetGoalSuccess(block_id, progress)
+                                }
+                                Ok(ApiResponse::Failure(error)) => Msg::GetGoalFailure(error),
+                                Err(error) => Msg::GetStatsError(error.to_string()),
+                            }
+                        },
+                    );
+                    self.block_tasks.push(api::get_skillblock_goal(
+                        block_id,
+                        query.clone(),
+                        handler,
+                    ));
+
                     self.state.skill_blocks.push(skillblock);
                     self.state.get_skillblocks_loaded = true;
                 }
 
                 true
             }
+            Msg::GetStatsSuccess(block_id, stats) => {
+                self.state.stats.insert(block_id, stats);
+                true
+            }
+            Msg::GetStatsError(message) => {
+                // Grid is still usable without stats, so only log error
+                ConsoleService::error(&message);
+                false
+            }
+            Msg::GetGoalSuccess(block_id, progress) => {
+                self.state
+                    .goal_met_days
+                    .insert(block_id, progress.met_days());
+                true
+            }
+            Msg::GetGoalFailure(error) => {
+                // Skillblocks without a goal are simply left unmarked
+                if error.code != "goal_not_found" {
+                    ConsoleService::error(&error.message);
+                }
+                false
+            }
+            Msg::ShowView(view) => {
+                // Replace time data of previous view with data of newly selected span
+                self.state.view = view;
+                self.state.skill_blocks.clear();
+                self.state.stats.clear();
+                self.state.goal_met_days.clear();
+                self.block_tasks.clear();
+                self.state.get_skillblocks_failure = None;
+                self.link.send_message(Msg::GetDevSkillBlock);
+
+                true
+            }
             Msg::UnauthorizedAccess => {
                 //TODO: Implement logic to destory session state
                 // stored on frontend
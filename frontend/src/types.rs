@@ -1,19 +1,16 @@
-use chrono::NaiveDateTime;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-// Struct represents different color options for heatmap shading
+// Colors drawn on top of skillblock palettes
 //TODO: Explore enum equivalent
 #[non_exhaustive]
 pub struct Color;
 
 impl Color {
-    pub const NEUTRAL: &'static str = "#dadada";
-    pub const LIGHT: &'static str = "#dac695";
-    pub const LIGHTMEDIUM: &'static str = "#f28a00";
-    pub const MEDIUM: &'static str = "#fd4600";
-    pub const MEDIUMHIGH: &'static str = "#f1230b";
-    pub const HIGH: &'static str = "#bc1c2a";
+    // Outline of days counting towards a met goal. Drawn
+    // around the intensity fill rather than replacing it
+    pub const GOAL_MET: &'static str = "#2f9e44";
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -31,17 +28,160 @@ pub struct Session {
 // Incoming timedata payload deserializes to this struct
 #[derive(Deserialize, Serialize)]
 pub struct TimeData {
+    pub block_id: i32,
     pub category: String,
     pub skill_name: String,
     pub skill_description: String,
     pub time_data: HashMap<NaiveDateTime, i32>,
+    pub last_synced_at: Option<NaiveDateTime>,
+    pub sync_status: String,
+    pub appearance: Appearance,
 }
 
-// Store various stat calculations from user time data
-pub struct TimeStats {
+// How block grid of a skillblock is shaded, as configured in backend.
+// Colors hold the no time data color followed by one color per shade,
+// thresholds hold the upper bound in minutes of every shade but the last
+#[derive(Deserialize, Serialize)]
+pub struct Appearance {
+    pub palette: String,
+    pub colors: Vec<String>,
+    pub intensity_scale: String,
+    pub thresholds: Vec<i32>,
+}
+
+impl Appearance {
+    // Color of a day holding given minutes of time data
+    pub fn color_for(&self, minutes: i32) -> &str {
+        let level = if minutes <= 0 {
+            0
+        } else {
+            self.thresholds
+                .iter()
+                .position(|threshold| minutes <= *threshold)
+                .map_or(self.thresholds.len() + 1, |index| index + 1)
+        };
+
+        self.colors
+            .get(level)
+            .or_else(|| self.colors.last())
+            .map_or("none", |color| color.as_str())
+    }
+}
+
+// Error payload sent by backend api. Code is stable and safe to match on
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+}
+
+// Span of time shown by block grid. Rolling view covers the
+// trailing year up to today, year view covers a calendar year
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridView {
+    Rolling,
+    Year(i32),
+}
+
+impl GridView {
+    // First and last day shown by grid
+    pub fn range(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match *self {
+            GridView::Rolling => {
+                // Leap days fall back to February 28th of previous year
+                let start = NaiveDate::from_ymd_opt(today.year() - 1, today.month(), today.day())
+                    .unwrap_or_else(|| NaiveDate::from_ymd(today.year() - 1, today.month(), 28));
+                (start, today)
+            }
+            GridView::Year(year) => (
+                NaiveDate::from_ymd(year, 1, 1),
+                NaiveDate::from_ymd(year, 12, 31),
+            ),
+        }
+    }
+
+    // Query string requesting time data shown by grid from backend
+    pub fn query(&self, today: NaiveDate) -> String {
+        match *self {
+            GridView::Rolling => format!("from={}", self.range(today).0),
+            GridView::Year(year) => format!("year={}", year),
+        }
+    }
+}
+
+// Run of consecutive days having time data
+#[derive(Deserialize, Serialize)]
+pub struct Streak {
+    pub days: i32,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+// Total seconds of a week or month, keyed by its first day
+#[derive(Deserialize, Serialize)]
+pub struct PeriodTotal {
+    pub start: NaiveDate,
+    pub seconds: i32,
+}
+
+// Stat calculations of skillblock time data served by backend.
+// Times are in seconds
+#[derive(Deserialize, Serialize)]
+pub struct SkillblockStats {
+    pub current_streak: Streak,
+    pub longest_streak: Streak,
+    pub active_days: i32,
+    pub total_seconds: i32,
     pub daily_max: i32,
-    pub yearly_max: i32,
-    pub longest_chain: i32,
+    pub daily_mean: i32,
+    pub daily_median: i32,
+    pub weekly_totals: Vec<PeriodTotal>,
+    pub monthly_totals: Vec<PeriodTotal>,
+}
+
+// Time target of a skillblock. Period is either "daily" or "weekly"
+#[derive(Deserialize, Serialize)]
+pub struct Goal {
+    pub id: i32,
+    pub block_id: i32,
+    pub period: String,
+    pub target_minutes: i32,
+    pub created_at: NaiveDateTime,
+}
+
+// Whether goal target was reached within a single period.
+// Status is "met", "missed" or "in_progress"
+#[derive(Deserialize, Serialize)]
+pub struct PeriodProgress {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub seconds: i32,
+    pub status: String,
+}
+
+// Goal of a skillblock along with its progress over span of block grid
+#[derive(Deserialize, Serialize)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub met: i32,
+    pub missed: i32,
+    pub periods: Vec<PeriodProgress>,
+}
+
+impl GoalProgress {
+    // Every day falling within a period the goal was met in
+    pub fn met_days(&self) -> HashSet<NaiveDate> {
+        let mut days = HashSet::new();
+        for period in self.periods.iter().filter(|period| period.status == "met") {
+            let mut day = period.start;
+            while day <= period.end {
+                days.insert(day);
+                day = day + Duration::days(1);
+            }
+        }
+
+        days
+    }
 }
 
 // Prototype struct being used to test handling request with multiple TimeData response objects. May remove at the conclusion of tests
@@ -0,0 +1,2 @@
+ALTER TABLE skillblocks
+DROP COLUMN source;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE skillblocks
+ADD COLUMN source VARCHAR NOT NULL DEFAULT 'rescuetime';
\ No newline at end of file
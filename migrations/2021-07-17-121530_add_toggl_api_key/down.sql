@@ -0,0 +1,2 @@
+ALTER TABLE users
+DROP COLUMN toggl_api_key;
\ No newline at end of file
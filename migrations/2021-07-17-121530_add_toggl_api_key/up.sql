@@ -0,0 +1,2 @@
+ALTER TABLE users
+ADD COLUMN toggl_api_key VARCHAR;
\ No newline at end of file
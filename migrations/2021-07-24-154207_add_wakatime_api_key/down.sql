@@ -0,0 +1,2 @@
+ALTER TABLE users
+DROP COLUMN wakatime_api_key;
\ No newline at end of file
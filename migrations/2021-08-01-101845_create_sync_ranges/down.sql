@@ -0,0 +1 @@
+DROP TABLE sync_ranges
\ No newline at end of file
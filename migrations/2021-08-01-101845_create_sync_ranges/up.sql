@@ -0,0 +1,11 @@
+CREATE TABLE sync_ranges (
+    id SERIAL PRIMARY KEY,
+    block_id INT NOT NULL,
+    range_start DATE NOT NULL,
+    range_end DATE NOT NULL,
+    completed_at TIMESTAMP NOT NULL,
+    CONSTRAINT fk_skillblocks
+        FOREIGN KEY(block_id)
+            REFERENCES skillblocks(block_id)
+            ON DELETE CASCADE
+)
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE skillblocks
+DROP COLUMN last_synced_at,
+DROP COLUMN sync_status;
\ No newline at end of file
@@ -0,0 +1,7 @@
+ALTER TABLE skillblocks
+ADD COLUMN last_synced_at TIMESTAMP,
+ADD COLUMN sync_status VARCHAR NOT NULL DEFAULT 'pending';
+
+UPDATE skillblocks
+SET sync_status = 'manual'
+WHERE source = 'manual';
\ No newline at end of file
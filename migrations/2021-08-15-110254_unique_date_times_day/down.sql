@@ -0,0 +1 @@
+DROP INDEX date_times_block_id_day_date_idx;
\ No newline at end of file
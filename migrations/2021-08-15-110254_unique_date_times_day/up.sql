@@ -0,0 +1,9 @@
+-- Keep most recently written row of each skillblock day
+DELETE FROM date_times a
+USING date_times b
+WHERE a.block_id = b.block_id
+    AND a.day_date = b.day_date
+    AND a.id < b.id;
+
+CREATE UNIQUE INDEX date_times_block_id_day_date_idx
+ON date_times (block_id, day_date);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE goals
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE goals (
+    id SERIAL PRIMARY KEY,
+    block_id INT NOT NULL UNIQUE,
+    period VARCHAR NOT NULL,
+    target_minutes INT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_skillblocks
+        FOREIGN KEY(block_id)
+            REFERENCES skillblocks(block_id)
+            ON DELETE CASCADE,
+    CONSTRAINT goals_period_check CHECK (period IN ('daily', 'weekly')),
+    CONSTRAINT goals_target_minutes_check CHECK (target_minutes > 0)
+)
\ No newline at end of file
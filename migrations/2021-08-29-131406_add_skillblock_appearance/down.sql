@@ -0,0 +1,4 @@
+ALTER TABLE skillblocks
+DROP COLUMN palette,
+DROP COLUMN intensity_scale,
+DROP COLUMN thresholds;
\ No newline at end of file
@@ -0,0 +1,4 @@
+ALTER TABLE skillblocks
+ADD COLUMN palette VARCHAR NOT NULL DEFAULT 'classic',
+ADD COLUMN intensity_scale VARCHAR NOT NULL DEFAULT 'fixed',
+ADD COLUMN thresholds INT[] NOT NULL DEFAULT '{15,30,45,60}';
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE skillblocks
+DROP COLUMN share_token;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE skillblocks
+ADD COLUMN share_token VARCHAR UNIQUE;
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Dropping data keys would leave sealed credentials unreadable, so
+-- rolling back is refused while any row still holds sealed credentials
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users WHERE wrapped_data_key IS NOT NULL) THEN
+        RAISE EXCEPTION 'Users hold sealed credentials, which would be lost without their data keys';
+    END IF;
+END
+$$;
+
+ALTER TABLE users
+DROP COLUMN credentials_key_id,
+DROP COLUMN wrapped_data_key;
\ No newline at end of file
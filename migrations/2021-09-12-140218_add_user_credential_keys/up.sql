@@ -0,0 +1,3 @@
+ALTER TABLE users
+ADD COLUMN credentials_key_id VARCHAR,
+ADD COLUMN wrapped_data_key VARCHAR;
\ No newline at end of file
@@ -0,0 +1,15 @@
+CREATE TABLE sessions (
+    token_hash VARCHAR PRIMARY KEY,
+    auth_id VARCHAR NOT NULL,
+    block_count INT NOT NULL,
+    email VARCHAR NOT NULL,
+    expires BIGINT NOT NULL,
+    given_name VARCHAR NOT NULL,
+    key_present BOOLEAN NOT NULL,
+    nickname VARCHAR NOT NULL,
+    picture VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX sessions_auth_id_idx ON sessions (auth_id);
+CREATE INDEX sessions_expires_idx ON sessions (expires);
\ No newline at end of file
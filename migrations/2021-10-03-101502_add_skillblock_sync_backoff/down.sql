@@ -0,0 +1,3 @@
+ALTER TABLE skillblocks
+DROP COLUMN sync_failures,
+DROP COLUMN next_attempt_at;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE skillblocks
+ADD COLUMN sync_failures INT NOT NULL DEFAULT 0,
+ADD COLUMN next_attempt_at TIMESTAMP;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE deletion_tokens;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE deletion_tokens (
+    user_id INT PRIMARY KEY,
+    token_hash VARCHAR NOT NULL,
+    expires BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(user_id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX deletion_tokens_expires_idx ON deletion_tokens (expires);
\ No newline at end of file